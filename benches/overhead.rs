@@ -0,0 +1,45 @@
+//!
+//! Per-item overhead of wrapping a hot loop in a progress bar, with and without
+//! batched updates. Run with `cargo bench --bench overhead`.
+//!
+use cpbar::ProgressBar;
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+const ITEMS: u32 = 10_000_000;
+
+fn report(name: &str, elapsed: Duration) {
+    println!(
+        "{:<24} {:>10.2} ns/item",
+        name,
+        elapsed.as_nanos() as f64 / ITEMS as f64
+    );
+}
+
+fn time(items: impl Iterator<Item = u32>) -> Duration {
+    let start = Instant::now();
+    let mut sum = 0u32;
+    for item in items {
+        sum = sum.wrapping_add(black_box(item));
+    }
+    black_box(sum);
+    start.elapsed()
+}
+
+/// Bar whose frames are not drawn, to measure the counting overhead alone.
+fn hidden() -> ProgressBar<std::ops::Range<u32>, cpbar::Bounded> {
+    let bar = ProgressBar::new(0..ITEMS).with_bounds();
+    bar.set_hidden(true);
+    bar
+}
+
+fn main() {
+    report("plain loop", time(0..ITEMS));
+    report("per item", time(hidden()));
+    for batch in [64, 1024, 65536].iter() {
+        report(
+            &format!("batch of {}", batch),
+            time(hidden().with_batch(*batch)),
+        );
+    }
+}
@@ -0,0 +1,42 @@
+//!
+//! Render benchmarks for the built-in templates and duration formats.
+//! Run with `cargo bench --features bench`.
+//!
+use cpbar::{bench::render_frames, DurationFormat, ProgressBar};
+use std::time::Duration;
+
+const FRAMES: usize = 100_000;
+
+fn report(name: &str, elapsed: Duration) {
+    println!(
+        "{:<24} {:>10.1} ns/frame",
+        name,
+        elapsed.as_nanos() as f64 / FRAMES as f64
+    );
+}
+
+fn main() {
+    report("unbounded", render_frames(ProgressBar::new(0..), FRAMES));
+    report(
+        "bounded",
+        render_frames(ProgressBar::new(0..1_000).with_bounds(), FRAMES),
+    );
+    report(
+        "bounded hms",
+        render_frames(
+            ProgressBar::new(0..1_000)
+                .with_bounds()
+                .with_duration_format(DurationFormat::Hms),
+            FRAMES,
+        ),
+    );
+    report(
+        "bounded custom template",
+        render_frames(
+            ProgressBar::new(0..1_000)
+                .with_bounds()
+                .with_template("{bar} {percent}% {pos}/{len} eta {eta}"),
+            FRAMES,
+        ),
+    );
+}
@@ -0,0 +1,39 @@
+//!
+//! Rendering benchmarks. Available with the `bench` feature.
+//!
+//! Measures how long a configured progress bar takes to render and write its
+//! frames, so the overhead of a style or template can be quantified before
+//! shipping it:
+//!
+//! ```
+//! use cpbar::*;
+//!
+//! let bar = ProgressBar::new(0..100)
+//!     .with_bounds()
+//!     .with_template("{bar} {percent}% eta {eta}");
+//! let elapsed = cpbar::bench::render_frames(bar, 1_000);
+//! println!("{:?} per frame", elapsed / 1_000);
+//! ```
+//!
+use crate::state::lock;
+use crate::term::ERASE_LINE;
+use crate::ProgressBar;
+use std::io::Write;
+use std::time::{Duration, Instant};
+
+/// Renders `frames` synthetic frames of the given bar to a null sink and
+/// returns the total time spent. The bar position cycles through its bound,
+/// or simply increases for unbounded bars. The inner iterator is not consumed.
+pub fn render_frames<Iter, Bound>(bar: ProgressBar<Iter, Bound>, frames: usize) -> Duration {
+    let mut sink = std::io::sink();
+    let mut state = lock(&bar.state);
+    let cycle = state.length.map_or(u64::MAX, |len| len + 1);
+    let start = Instant::now();
+    for frame in 0..frames {
+        state.index = frame;
+        state.position = frame as u64 % cycle;
+        let line = state.render();
+        let _ = writeln!(sink, "{}{}", ERASE_LINE, line);
+    }
+    start.elapsed()
+}
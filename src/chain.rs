@@ -0,0 +1,59 @@
+//!
+//! Several iterators consumed one after the other as a single sequence.
+//!
+use std::collections::VecDeque;
+
+/// Iterator over the items of several iterators in order, with an exact
+/// length: the sum of theirs. Built by [`ProgressBar::chain`](crate::ProgressBar::chain).
+///
+/// # Example
+/// ```
+/// use cpbar::*;
+///
+/// let chain = Chain::new(vec![0..2, 10..13]);
+/// assert_eq!(chain.len(), 5);
+/// assert_eq!(chain.collect::<Vec<_>>(), [0, 1, 10, 11, 12]);
+/// ```
+pub struct Chain<It> {
+    parts: VecDeque<It>,
+    len: usize,
+}
+
+impl<It> Chain<It>
+where
+    It: ExactSizeIterator,
+{
+    /// Chains the given iterators.
+    pub fn new<I>(iters: I) -> Self
+    where
+        I: IntoIterator<Item = It>,
+    {
+        let parts: VecDeque<It> = iters.into_iter().collect();
+        let len = parts.iter().map(ExactSizeIterator::len).sum();
+        Self { parts, len }
+    }
+}
+
+impl<It> Iterator for Chain<It>
+where
+    It: ExactSizeIterator,
+{
+    type Item = It::Item;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(part) = self.parts.front_mut() {
+            if let Some(item) = part.next() {
+                self.len -= 1;
+                return Some(item);
+            }
+            self.parts.pop_front();
+        }
+        None
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<It> ExactSizeIterator for Chain<It> where It: ExactSizeIterator {}
@@ -0,0 +1,66 @@
+//!
+//! Progress saved to disk so an interrupted job can resume where it stopped.
+//!
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::time::Duration;
+
+/// Progress of a bar as recorded in a checkpoint file, one `key value` pair
+/// per line.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Checkpoint {
+    pub(crate) position: u64,
+    pub(crate) total: Option<u64>,
+    pub(crate) elapsed: Duration,
+}
+
+impl Checkpoint {
+    /// Writes the checkpoint to `path`, replacing the previous one atomically
+    /// so a job killed while saving does not leave a truncated file behind.
+    pub(crate) fn save(&self, path: &Path) -> io::Result<()> {
+        let mut text = format!("position {}\n", self.position);
+        if let Some(total) = self.total {
+            text.push_str(&format!("total {}\n", total));
+        }
+        text.push_str(&format!("elapsed_secs {:.6}\n", self.elapsed.as_secs_f64()));
+        let mut partial = path.as_os_str().to_owned();
+        partial.push(".partial");
+        fs::write(&partial, text)?;
+        fs::rename(&partial, path)
+    }
+
+    /// Reads the checkpoint at `path`, `None` if there is none yet.
+    pub(crate) fn load(path: &Path) -> io::Result<Option<Self>> {
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(error) => return Err(error),
+        };
+        let mut checkpoint = Self {
+            position: 0,
+            total: None,
+            elapsed: Duration::ZERO,
+        };
+        for line in text.lines().filter(|line| !line.trim().is_empty()) {
+            let invalid = || {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("invalid checkpoint line `{}`", line),
+                )
+            };
+            let (key, value) = line.split_once(' ').ok_or_else(invalid)?;
+            match key {
+                "position" => checkpoint.position = value.parse().map_err(|_| invalid())?,
+                "total" => checkpoint.total = Some(value.parse().map_err(|_| invalid())?),
+                "elapsed_secs" => {
+                    let secs: f64 = value.parse().map_err(|_| invalid())?;
+                    checkpoint.elapsed =
+                        Duration::try_from_secs_f64(secs).map_err(|_| invalid())?;
+                }
+                _ => return Err(invalid()),
+            }
+        }
+        Ok(Some(checkpoint))
+    }
+}
@@ -0,0 +1,340 @@
+//!
+//! Console output of a progress bar, either on its own line or as one of the
+//! lines of a [`MultiProgress`](crate::MultiProgress).
+//!
+use crate::ext;
+use crate::multi::{self, BarId, MultiDraw};
+use crate::sink::Mirror;
+use crate::state::{lock, try_lock, BarState};
+use crate::term::{self, ERASE_CURRENT_LINE, ERASE_LINE};
+use std::sync::{Arc, Mutex};
+
+/// How a standalone bar replaces its previous frame, see
+/// [`ProgressBar::with_redraw_mode`](crate::ProgressBar::with_redraw_mode).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RedrawMode {
+    /// Each frame ends with a newline, the next one moves the cursor up to
+    /// erase it.
+    #[default]
+    CursorUp,
+    /// Frames are redrawn in place with a carriage return and no newline until
+    /// the bar finishes, for terminals and multiplexers that mishandle cursor
+    /// movements.
+    SameLine,
+}
+
+fn flush() {
+    let _ = std::io::Write::flush(&mut std::io::stdout());
+}
+
+/// Owns the console line of a progress bar and its mirrors.
+pub(crate) struct DrawTarget {
+    last_frame: Option<String>,
+    finished: bool,
+    pub(crate) multi: Option<Arc<Mutex<MultiDraw>>>,
+    /// Output replacing the console.
+    pub(crate) backend: Option<Box<dyn ext::DrawTarget>>,
+    pub(crate) mirrors: Vec<Mirror>,
+    pub(crate) clear_on_finish: bool,
+    pub(crate) mode: RedrawMode,
+    /// Number of `suspend` calls running, the bar is not drawn meanwhile.
+    suspended: usize,
+    /// Whether the console does not show the last frame, erased or drawn
+    /// while suspended.
+    pending: bool,
+}
+
+impl DrawTarget {
+    pub(crate) fn new() -> Self {
+        term::enable();
+        Self {
+            last_frame: None,
+            finished: false,
+            multi: None,
+            backend: None,
+            mirrors: Vec::new(),
+            clear_on_finish: false,
+            mode: RedrawMode::default(),
+            suspended: 0,
+            pending: false,
+        }
+    }
+
+    /// Replaces the bar line with a new frame.
+    pub(crate) fn draw(&mut self, id: BarId, frame: String) {
+        for mirror in &mut self.mirrors {
+            mirror.update(&frame);
+        }
+        if let Some(backend) = &mut self.backend {
+            backend.draw(&frame);
+            self.last_frame = Some(frame);
+            return;
+        }
+        match &self.multi {
+            Some(multi) => lock(multi).update(id, frame.clone()),
+            None if self.suspended > 0 => self.pending = true,
+            None => {
+                print!("{}", self.redraw(&frame));
+                flush();
+            }
+        }
+        self.last_frame = Some(frame);
+    }
+
+    /// Output replacing the previous frame of a standalone bar with `frame`.
+    /// The previous line is erased whole, so a shorter frame leaves nothing
+    /// of the previous one behind.
+    fn redraw(&self, frame: &str) -> String {
+        match self.mode {
+            RedrawMode::SameLine => format!("{}{}", ERASE_CURRENT_LINE, frame),
+            RedrawMode::CursorUp if self.last_frame.is_some() => {
+                format!("{}{}\n", ERASE_LINE, frame)
+            }
+            RedrawMode::CursorUp => format!("{}\n", frame),
+        }
+    }
+
+    /// Writes the frames the mirrors coalesced once their interval elapsed.
+    pub(crate) fn tick_mirrors(&mut self) {
+        for mirror in &mut self.mirrors {
+            mirror.tick();
+        }
+    }
+
+    /// Writes terminal control sequences that are not part of a frame, such
+    /// as the OS progress indicator. Only the console gets them: custom
+    /// targets and mirrors receive text only.
+    pub(crate) fn control(&mut self, sequences: &str) {
+        if sequences.is_empty() || self.backend.is_some() {
+            return;
+        }
+        print!("{}", sequences);
+        flush();
+    }
+
+    /// Prints a persistent line above the bar and redraws the bar below it.
+    /// Once a standalone bar is finished lines are simply printed after it.
+    pub(crate) fn println(&mut self, message: &str) {
+        if let Some(backend) = &mut self.backend {
+            return backend.println(message);
+        }
+        match &self.multi {
+            Some(multi) => lock(multi).println(message),
+            None if self.finished || self.suspended > 0 => println!("{}", message),
+            None => match &self.last_frame {
+                Some(frame) if self.mode == RedrawMode::SameLine => {
+                    print!("{}{}\n{}", ERASE_CURRENT_LINE, message, frame);
+                    flush();
+                }
+                Some(frame) => println!("{}{}\n{}", ERASE_LINE, message, frame),
+                None => println!("{}", message),
+            },
+        }
+    }
+
+    /// Erases the bar line and stops drawing it until `resume`.
+    fn pause(&mut self) {
+        self.suspended += 1;
+        let shown = !self.finished && !self.pending && self.last_frame.is_some();
+        if self.suspended > 1 || self.backend.is_some() || !shown {
+            return;
+        }
+        match self.mode {
+            RedrawMode::SameLine => print!("{}", ERASE_CURRENT_LINE),
+            RedrawMode::CursorUp => print!("{}", ERASE_LINE),
+        }
+        flush();
+        self.pending = true;
+    }
+
+    /// Draws the last frame again once the last `suspend` call is over.
+    fn resume(&mut self) {
+        self.suspended -= 1;
+        if self.suspended > 0 || !self.pending {
+            return;
+        }
+        self.pending = false;
+        if let Some(frame) = &self.last_frame {
+            match self.mode {
+                RedrawMode::SameLine if !self.finished => print!("{}", frame),
+                _ => println!("{}", frame),
+            }
+            flush();
+        }
+    }
+
+    /// Ends the output of a bar dropped before finishing. A panic tears the
+    /// whole multi-bar display down as its lines would otherwise be redrawn
+    /// over the panic message.
+    pub(crate) fn abandon(&mut self) {
+        self.finished = true;
+        for mirror in &mut self.mirrors {
+            mirror.flush();
+        }
+        if let Some(backend) = &mut self.backend {
+            return backend.finish();
+        }
+        match &self.multi {
+            Some(multi) if std::thread::panicking() => {
+                if let Some(mut multi) = try_lock(multi) {
+                    multi.teardown();
+                }
+            }
+            Some(_) => {}
+            None if self.mode == RedrawMode::SameLine && self.last_frame.is_some() => {
+                if self.pending {
+                    // Drawn with its line ended once resumed.
+                    return;
+                }
+                println!();
+                flush();
+            }
+            None => {}
+        }
+    }
+
+    /// Lets a finished bar start over, drawing on its previous line.
+    pub(crate) fn reset(&mut self) {
+        // Finishing ended the line of a bar redrawn in place, go back to it.
+        let ended = self.finished && self.mode == RedrawMode::SameLine;
+        let shown = self.last_frame.is_some() && !self.pending;
+        if ended && self.multi.is_none() && self.backend.is_none() && shown {
+            print!("{}", ERASE_LINE);
+            flush();
+        }
+        self.finished = false;
+    }
+
+    /// Flushes the mirrors and erases the bar line if requested.
+    pub(crate) fn finish(&mut self, id: BarId) {
+        self.finished = true;
+        for mirror in &mut self.mirrors {
+            mirror.flush();
+        }
+        if let Some(backend) = &mut self.backend {
+            return backend.finish();
+        }
+        if self.multi.is_none() && self.pending {
+            // Drawn once resumed, unless it is cleared.
+            if self.clear_on_finish {
+                self.last_frame = None;
+                self.pending = false;
+            }
+            return;
+        }
+        // A bar redrawn in place still has to end its line.
+        if self.multi.is_none() && self.mode == RedrawMode::SameLine && self.last_frame.is_some() {
+            if self.clear_on_finish {
+                print!("{}", ERASE_CURRENT_LINE);
+                self.last_frame = None;
+            } else {
+                println!();
+            }
+            return flush();
+        }
+        if self.clear_on_finish {
+            match &self.multi {
+                Some(multi) => lock(multi).remove(id),
+                None if self.last_frame.is_some() => print!("{}", ERASE_LINE),
+                None => {}
+            }
+            self.last_frame = None;
+        }
+    }
+}
+
+/// Erases the bar of `state`, runs `f` and draws the bar again below whatever
+/// `f` printed. The bar is not locked while `f` runs, so `f` can update it:
+/// its new state is drawn once `f` returns.
+pub(crate) fn suspend<R>(state: &Mutex<BarState>, f: impl FnOnce() -> R) -> R {
+    /// Resumes even if `f` panics.
+    struct Resume<'a>(&'a Mutex<BarState>);
+    impl Drop for Resume<'_> {
+        fn drop(&mut self) {
+            lock(self.0).target.resume();
+        }
+    }
+
+    let multi = lock(state).target.multi.clone();
+    if let Some(multi) = multi {
+        return multi::suspend(&multi, f);
+    }
+    lock(state).target.pause();
+    let _resume = Resume(state);
+    f()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Lines left on a screen after writing `output`, interpreting the cursor
+    /// moves and erasures written by the console renderer.
+    fn screen(output: &str) -> Vec<String> {
+        let mut lines: Vec<Vec<char>> = vec![Vec::new()];
+        let (mut row, mut column) = (0, 0);
+        let mut rest = output;
+        while let Some(c) = rest.chars().next() {
+            if let Some(sequence) = ["\x1b[1A", "\x1b[2K", "\x1b[0J"]
+                .iter()
+                .find(|sequence| rest.starts_with(**sequence))
+            {
+                match *sequence {
+                    "\x1b[1A" => row -= 1,
+                    "\x1b[2K" => lines[row].clear(),
+                    _ => {
+                        lines[row].truncate(column);
+                        lines.truncate(row + 1);
+                    }
+                }
+                rest = &rest[sequence.len()..];
+                continue;
+            }
+            match c {
+                '\r' => column = 0,
+                '\n' => {
+                    row += 1;
+                    column = 0;
+                    if row == lines.len() {
+                        lines.push(Vec::new());
+                    }
+                }
+                c => {
+                    let line = &mut lines[row];
+                    line.resize(line.len().max(column), ' ');
+                    match line.get_mut(column) {
+                        Some(cell) => *cell = c,
+                        None => line.push(c),
+                    }
+                    column += 1;
+                }
+            }
+            rest = &rest[c.len_utf8()..];
+        }
+        lines.iter().map(|line| line.iter().collect()).collect()
+    }
+
+    fn drawn(mode: RedrawMode, frames: &[&str]) -> Vec<String> {
+        let mut target = DrawTarget::new();
+        target.mode = mode;
+        let mut output = String::new();
+        for frame in frames {
+            output.push_str(&target.redraw(frame));
+            target.last_frame = Some(frame.to_string());
+        }
+        screen(&output)
+    }
+
+    #[test]
+    fn shorter_frames_leave_nothing_behind() {
+        let frames = ["[##  ] 1/3 600ms", "[####] 2/3 1.2s"];
+        assert_eq!(drawn(RedrawMode::CursorUp, &frames), ["[####] 2/3 1.2s", ""]);
+        assert_eq!(drawn(RedrawMode::SameLine, &frames), ["[####] 2/3 1.2s"]);
+    }
+
+    #[test]
+    fn frames_replace_the_previous_line() {
+        let frames = ["first", "second", "third"];
+        assert_eq!(drawn(RedrawMode::CursorUp, &frames), ["third", ""]);
+    }
+}
@@ -0,0 +1,47 @@
+//!
+//! Errors reported when validating the configuration of a bar.
+//!
+use crate::rate::RateEstimator;
+use std::fmt;
+
+/// Nonsensical configuration detected by
+/// [`ProgressBar::try_build`](crate::ProgressBar::try_build).
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum ConfigError {
+    /// The bar style draws filled and empty cells alike, typically because
+    /// its charset is empty.
+    EmptyCharset,
+    /// A field was given a width of zero columns: `"message"`, `"prefix"` or
+    /// `"line"`.
+    ZeroWidth(&'static str),
+    /// The smoothing factor is not between 0 (excluded) and 1.
+    InvalidSmoothing(f64),
+    /// The rate estimator has a weight outside of 0 to 1, or a window that is
+    /// empty, infinite or too long for a `Duration`.
+    InvalidRateEstimator(RateEstimator),
+    /// The timing statistics are limited to zero samples.
+    ZeroSampleLimit,
+    /// The steady tick interval is zero, which would redraw the bar in a
+    /// busy loop.
+    ZeroTickInterval,
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::EmptyCharset => write!(f, "the bar style cannot show any progress"),
+            ConfigError::ZeroWidth(field) => write!(f, "the {} width is zero", field),
+            ConfigError::InvalidSmoothing(factor) => {
+                write!(f, "smoothing factor {} is not within (0, 1]", factor)
+            }
+            ConfigError::InvalidRateEstimator(estimator) => {
+                write!(f, "invalid rate estimator {:?}", estimator)
+            }
+            ConfigError::ZeroSampleLimit => write!(f, "the sample limit is zero"),
+            ConfigError::ZeroTickInterval => write!(f, "the steady tick interval is zero"),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
@@ -0,0 +1,241 @@
+//!
+//! Extension API for crates providing styles, backends or estimators.
+//!
+//! The items of this module are the supported way to plug into a progress bar
+//! and follow semantic versioning like the rest of the public API: within a
+//! major version [`ProgressState`] only gains fields (it is `#[non_exhaustive]`)
+//! and the traits only gain methods with a default implementation. Everything
+//! marked `#[doc(hidden)]` elsewhere in the crate is internal and may change in
+//! any release.
+//!
+//! * [`Component`]: renders a custom `{name}` template field.
+//! * [`Renderer`]: draws the whole bar in place of the built-in renderer.
+//! * [`DrawTarget`]: receives the frames instead of the console.
+//! * [`EtaEstimator`]: computes the `{eta}` field.
+//! * [`Observer`]: gets notified as the bar progresses.
+//! * [`Clock`]: tells the time the bar measures its progress with.
+//!
+use crate::format::json_string;
+use crate::multi::BarId;
+use std::io::{self, Write};
+use std::time::{Duration, Instant};
+
+/// Read-only view of a progress bar handed to extensions.
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub struct ProgressState {
+    /// Identifier of the bar.
+    pub id: BarId,
+    /// Progress made so far, see [`ProgressBar::position`](crate::ProgressBar::position).
+    pub position: u64,
+    /// Total to reach, `None` for unbounded bars.
+    pub length: Option<u64>,
+    /// Time since the bar started.
+    pub elapsed: Duration,
+    /// Units of progress per second, see
+    /// [`ProgressBar::with_rate_estimator`](crate::ProgressBar::with_rate_estimator).
+    pub rate: f64,
+    /// Built-in estimate of the time left, `None` when it is not known.
+    pub eta: Option<Duration>,
+    /// Number of failed items.
+    pub errors: u64,
+    /// Message displayed by the `{msg}` field.
+    pub message: String,
+    /// Whether the underlying iterator is exhausted.
+    pub finished: bool,
+}
+
+impl ProgressState {
+    /// Serializes the state as a JSON object, to ship it over HTTP or write it
+    /// to disk. Durations are in seconds, unknown values are `null`.
+    ///
+    /// # Example
+    /// ```
+    /// use cpbar::*;
+    ///
+    /// let mut progress_bar = ProgressBar::new(0..4).with_bounds();
+    /// progress_bar.by_ref().take(2).for_each(drop);
+    /// let json = progress_bar.snapshot().to_json();
+    /// assert!(json.starts_with("{\"id\":"));
+    /// assert!(json.contains(",\"position\":1,\"length\":4,\"elapsed_secs\":"));
+    /// assert!(json.ends_with(",\"errors\":0,\"message\":\"\",\"finished\":false}"));
+    /// ```
+    pub fn to_json(&self) -> String {
+        let length = match self.length {
+            Some(length) => length.to_string(),
+            None => "null".to_string(),
+        };
+        let eta = match self.eta {
+            Some(eta) => format!("{:.6}", eta.as_secs_f64()),
+            None => "null".to_string(),
+        };
+        format!(
+            concat!(
+                "{{\"id\":{},\"position\":{},\"length\":{},\"elapsed_secs\":{:.6},",
+                "\"rate\":{:.6},\"eta_secs\":{},\"errors\":{},\"message\":{},",
+                "\"finished\":{}}}"
+            ),
+            self.id.value(),
+            self.position,
+            length,
+            self.elapsed.as_secs_f64(),
+            self.rate,
+            eta,
+            self.errors,
+            json_string(&self.message),
+            self.finished
+        )
+    }
+}
+
+/// Serializes the same fields as [`ProgressState::to_json`], available with
+/// the `serde` feature. Durations are in seconds, unknown values are `None`.
+///
+/// # Example
+/// ```
+/// use cpbar::*;
+///
+/// let mut progress_bar = ProgressBar::new(0..4).with_bounds().with_message("copying");
+/// progress_bar.by_ref().take(2).for_each(drop);
+/// let snapshot = progress_bar.snapshot();
+/// let value = serde_json::to_value(&snapshot).unwrap();
+/// assert_eq!(value["position"], 1);
+/// assert_eq!(value["length"], 4);
+/// assert_eq!(value["message"], "copying");
+/// let json: serde_json::Value = serde_json::from_str(&snapshot.to_json()).unwrap();
+/// let keys = |value: &serde_json::Value| value.as_object().unwrap().keys().count();
+/// assert_eq!(keys(&value), keys(&json));
+/// assert!(json.as_object().unwrap().keys().all(|key| value.get(key).is_some()));
+/// ```
+#[cfg(feature = "serde")]
+impl serde::Serialize for ProgressState {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+
+        let mut state = serializer.serialize_struct("ProgressState", 9)?;
+        state.serialize_field("id", &self.id.value())?;
+        state.serialize_field("position", &self.position)?;
+        state.serialize_field("length", &self.length)?;
+        state.serialize_field("elapsed_secs", &self.elapsed.as_secs_f64())?;
+        state.serialize_field("rate", &self.rate)?;
+        state.serialize_field("eta_secs", &self.eta.map(|eta| eta.as_secs_f64()))?;
+        state.serialize_field("errors", &self.errors)?;
+        state.serialize_field("message", &self.message)?;
+        state.serialize_field("finished", &self.finished)?;
+        state.end()
+    }
+}
+
+/// Renders a custom template field, registered with
+/// [`ProgressBar::with_component`](crate::ProgressBar::with_component).
+/// Implemented for closures taking the state.
+pub trait Component: Send {
+    /// Text displayed in place of the field.
+    fn render(&self, state: &ProgressState) -> String;
+}
+
+impl<F> Component for F
+where
+    F: Fn(&ProgressState) -> String + Send,
+{
+    fn render(&self, state: &ProgressState) -> String {
+        self(state)
+    }
+}
+
+/// Draws a progress bar in place of the built-in template renderer, set with
+/// [`ProgressBar::with_renderer`](crate::ProgressBar::with_renderer). TUI
+/// widgets, web dashboards or loggers can draw a bar their own way from the
+/// state alone.
+///
+/// What a renderer writes is a frame: it replaces the previous frame on the
+/// target of the bar, the console, a [`DrawTarget`] or a line of a
+/// [`MultiProgress`](crate::MultiProgress), and is copied to the mirrors.
+/// Writing nothing keeps the previous frame.
+///
+/// # Example
+/// ```
+/// use cpbar::ext::{ProgressState, Renderer};
+/// use std::io::{self, Write};
+///
+/// /// Redraws the count every ten items.
+/// struct Counter;
+/// impl Renderer for Counter {
+///     fn render(&mut self, state: &ProgressState, out: &mut dyn Write) -> io::Result<()> {
+///         if state.position % 10 == 0 {
+///             write!(out, "processed {} items", state.position)?;
+///         }
+///         Ok(())
+///     }
+/// }
+/// ```
+pub trait Renderer: Send {
+    /// Writes the frame of the bar in its current state on `out`.
+    fn render(&mut self, state: &ProgressState, out: &mut dyn Write) -> io::Result<()>;
+
+    /// Called once the bar is finished, after its last frame. Anything
+    /// written on `out` is drawn as the final frame.
+    fn finish(&mut self, state: &ProgressState, out: &mut dyn Write) -> io::Result<()> {
+        let _ = (state, out);
+        Ok(())
+    }
+}
+
+/// Output of a progress bar replacing the console, set with
+/// [`ProgressBar::with_draw_target`](crate::ProgressBar::with_draw_target).
+pub trait DrawTarget: Send {
+    /// Displays a new frame, replacing the previous one.
+    fn draw(&mut self, frame: &str);
+
+    /// Displays a persistent line. Ignored by default.
+    fn println(&mut self, line: &str) {
+        let _ = line;
+    }
+
+    /// Called once the bar is finished, after its last frame.
+    fn finish(&mut self) {}
+}
+
+/// Computes the estimated time left displayed by the `{eta}` field, set with
+/// [`ProgressBar::with_eta_estimator`](crate::ProgressBar::with_eta_estimator).
+pub trait EtaEstimator: Send {
+    /// Called on every tick of the bar, before the frame is drawn.
+    fn update(&mut self, state: &ProgressState) {
+        let _ = state;
+    }
+
+    /// Estimated time left, `None` to display `?`.
+    fn eta(&self, state: &ProgressState) -> Option<Duration>;
+}
+
+/// Notified as a progress bar advances, registered with
+/// [`ProgressBar::with_observer`](crate::ProgressBar::with_observer).
+pub trait Observer: Send {
+    /// Called on every tick of the bar, after the frame is drawn.
+    fn on_tick(&mut self, state: &ProgressState) {
+        let _ = state;
+    }
+
+    /// Called once when the bar finishes.
+    fn on_finish(&mut self, state: &ProgressState) {
+        let _ = state;
+    }
+}
+
+/// Source of the current time of a progress bar, set with
+/// [`ProgressBar::with_clock`](crate::ProgressBar::with_clock). The elapsed
+/// time, the rate, the ETA and the item durations are measured with it, which
+/// lets tests control time, see [`testing::MockClock`](crate::testing::MockClock).
+pub trait Clock: Send + Sync {
+    /// Current time. Consecutive calls must not go back in time.
+    fn now(&self) -> Instant;
+}
+
+/// The system monotonic clock, used by default.
+pub(crate) struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
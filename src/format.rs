@@ -0,0 +1,713 @@
+//!
+//! Formatting helpers shared by the progress bar renderers.
+//!
+use crate::term::{paint, GREEN, RED};
+use std::cell::Cell;
+use std::time::Duration;
+
+const MINUTE: u64 = 60;
+const HOUR: u64 = 60 * MINUTE;
+const DAY: u64 = 24 * HOUR;
+
+/// Presets used to render durations such as the elapsed time and the ETA.
+///
+/// # Example
+/// ```
+/// use cpbar::DurationFormat;
+/// use std::time::Duration;
+///
+/// let time = Duration::from_secs(5 * 60 + 32);
+/// assert_eq!(DurationFormat::Hms.format(time), "00:05:32");
+/// assert_eq!(DurationFormat::HumanShort.format(time), "5m 32s");
+/// assert_eq!(DurationFormat::Adaptive.format(time), "5m32s");
+/// assert_eq!(DurationFormat::SecondsF64.format(time), "332.0000 Secs");
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DurationFormat {
+    /// Clock style `HH:MM:SS`. Durations of a day or more are prefixed by the
+    /// number of days: `1d 02:03:04`.
+    ///
+    /// ```
+    /// use cpbar::DurationFormat;
+    /// use std::time::Duration;
+    ///
+    /// assert_eq!(DurationFormat::Hms.format(Duration::from_secs(3_599)), "00:59:59");
+    /// assert_eq!(DurationFormat::Hms.format(Duration::from_secs(3_600)), "01:00:00");
+    /// assert_eq!(DurationFormat::Hms.format(Duration::from_secs(86_399)), "23:59:59");
+    /// assert_eq!(DurationFormat::Hms.format(Duration::from_secs(86_400)), "1d 00:00:00");
+    /// assert_eq!(DurationFormat::Hms.format(Duration::from_secs(93_784)), "1d 02:03:04");
+    /// ```
+    Hms,
+    /// Short human readable style keeping the two most significant units:
+    /// `32s`, `5m 32s`, `1h 05m`, `2d 03h`.
+    ///
+    /// ```
+    /// use cpbar::DurationFormat;
+    /// use std::time::Duration;
+    ///
+    /// assert_eq!(DurationFormat::HumanShort.format(Duration::from_millis(900)), "0s");
+    /// assert_eq!(DurationFormat::HumanShort.format(Duration::from_secs(59)), "59s");
+    /// assert_eq!(DurationFormat::HumanShort.format(Duration::from_secs(60)), "1m 00s");
+    /// assert_eq!(DurationFormat::HumanShort.format(Duration::from_secs(3_599)), "59m 59s");
+    /// assert_eq!(DurationFormat::HumanShort.format(Duration::from_secs(3_600)), "1h 00m");
+    /// assert_eq!(DurationFormat::HumanShort.format(Duration::from_secs(86_399)), "23h 59m");
+    /// assert_eq!(DurationFormat::HumanShort.format(Duration::from_secs(86_400)), "1d 00h");
+    /// assert_eq!(DurationFormat::HumanShort.format(Duration::from_secs(183_600)), "2d 03h");
+    /// ```
+    HumanShort,
+    /// Compact style with a precision adapted to the magnitude: milliseconds
+    /// under a second, tenths of seconds under a minute, then the two most
+    /// significant units. This is the default.
+    ///
+    /// ```
+    /// use cpbar::DurationFormat;
+    /// use std::time::Duration;
+    ///
+    /// assert_eq!(DurationFormat::Adaptive.format(Duration::from_millis(7)), "7ms");
+    /// assert_eq!(DurationFormat::Adaptive.format(Duration::from_millis(999)), "999ms");
+    /// assert_eq!(DurationFormat::Adaptive.format(Duration::from_millis(12_340)), "12.3s");
+    /// assert_eq!(DurationFormat::Adaptive.format(Duration::from_millis(59_960)), "59.9s");
+    /// assert_eq!(DurationFormat::Adaptive.format(Duration::from_secs(185)), "3m05s");
+    /// assert_eq!(DurationFormat::Adaptive.format(Duration::from_secs(3_720)), "1h02m");
+    /// assert_eq!(DurationFormat::Adaptive.format(Duration::from_secs(183_600)), "2d03h");
+    /// ```
+    #[default]
+    Adaptive,
+    /// Fractional seconds with four decimals: `0.3221 Secs`.
+    SecondsF64,
+}
+
+impl DurationFormat {
+    /// Formats the given duration using this preset.
+    pub fn format(&self, duration: Duration) -> String {
+        let secs = duration.as_secs();
+        match self {
+            DurationFormat::Hms => {
+                let clock = format!(
+                    "{:02}:{:02}:{:02}",
+                    (secs % DAY) / HOUR,
+                    (secs % HOUR) / MINUTE,
+                    secs % MINUTE
+                );
+                if secs >= DAY {
+                    format!("{}d {}", secs / DAY, clock)
+                } else {
+                    clock
+                }
+            }
+            DurationFormat::HumanShort => {
+                if secs >= DAY {
+                    format!("{}d {:02}h", secs / DAY, (secs % DAY) / HOUR)
+                } else if secs >= HOUR {
+                    format!("{}h {:02}m", secs / HOUR, (secs % HOUR) / MINUTE)
+                } else if secs >= MINUTE {
+                    format!("{}m {:02}s", secs / MINUTE, secs % MINUTE)
+                } else {
+                    format!("{}s", secs)
+                }
+            }
+            DurationFormat::Adaptive => {
+                if secs >= DAY {
+                    format!("{}d{:02}h", secs / DAY, (secs % DAY) / HOUR)
+                } else if secs >= HOUR {
+                    format!("{}h{:02}m", secs / HOUR, (secs % HOUR) / MINUTE)
+                } else if secs >= MINUTE {
+                    format!("{}m{:02}s", secs / MINUTE, secs % MINUTE)
+                } else if secs >= 1 {
+                    // Truncated like the other units, 59.96s is not a minute yet.
+                    format!("{}.{}s", secs, duration.subsec_millis() / 100)
+                } else {
+                    format!("{}ms", duration.subsec_millis())
+                }
+            }
+            DurationFormat::SecondsF64 => format!("{:.4} Secs", duration.as_secs_f64()),
+        }
+    }
+}
+
+const DECIMAL_PREFIXES: [&str; 5] = ["", "k", "M", "G", "T"];
+const BINARY_PREFIXES: [&str; 5] = ["", "Ki", "Mi", "Gi", "Ti"];
+
+/// Scaled units for the `{pos}`, `{len}` and `{rate}` fields, switching
+/// between prefixes (`items`, `k items`, `M items` or `B`, `KiB`, `MiB`) as
+/// the values grow, with a constant field width so the line does not jitter.
+///
+/// Switching to a smaller prefix only happens once the value falls below the
+/// hysteresis margin, so a value hovering around a boundary does not flip
+/// back and forth between two units.
+///
+/// # Example
+/// ```
+/// use cpbar::Units;
+///
+/// let bytes = Units::binary("B");
+/// assert_eq!(bytes.format(512.0), "   512   B");
+/// assert_eq!(bytes.format(1536.0), "  1.50 KiB");
+/// assert_eq!(bytes.format(3.0 * 1024.0 * 1024.0), "  3.00 MiB");
+///
+/// let items = Units::decimal("items");
+/// assert_eq!(items.format(999.0), "   999   items");
+/// assert_eq!(items.format(12_345.0), " 12.35 k items");
+/// // Rounding to 1000 takes the next prefix.
+/// assert_eq!(items.format(999_996.0), "  1.00 M items");
+///
+/// let pinned = Units::binary("B").fixed(2);
+/// assert_eq!(pinned.format(1536.0), "  0.00 MiB");
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Units {
+    symbol: String,
+    binary: bool,
+    hysteresis: f64,
+    fixed: Option<usize>,
+}
+
+impl Units {
+    /// Powers of 1000: `k`, `M`, `G`, `T`.
+    pub fn decimal(symbol: &str) -> Self {
+        Self {
+            symbol: symbol.to_string(),
+            binary: false,
+            hysteresis: 0.1,
+            fixed: None,
+        }
+    }
+
+    /// Powers of 1024: `Ki`, `Mi`, `Gi`, `Ti`.
+    pub fn binary(symbol: &str) -> Self {
+        Self {
+            binary: true,
+            ..Units::decimal(symbol)
+        }
+    }
+
+    /// Fraction under a unit boundary the value must fall to before switching
+    /// back to the smaller prefix. Defaults to `0.1`, `0.0` disables it.
+    pub fn with_hysteresis(mut self, hysteresis: f64) -> Self {
+        self.hysteresis = hysteresis.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Always uses the given prefix, `0` being no prefix, `1` kilo and so on.
+    pub fn fixed(mut self, level: usize) -> Self {
+        self.fixed = Some(level.min(DECIMAL_PREFIXES.len() - 1));
+        self
+    }
+
+    /// Formats a value picking the best prefix.
+    pub fn format(&self, value: f64) -> String {
+        self.scale(value, &Cell::new(0))
+    }
+
+    /// Formats a value, `level` remembering the prefix used on the previous
+    /// frame for the hysteresis.
+    pub(crate) fn scale(&self, value: f64, level: &Cell<usize>) -> String {
+        let base: f64 = if self.binary { 1024.0 } else { 1000.0 };
+        let prefixes = if self.binary {
+            BINARY_PREFIXES
+        } else {
+            DECIMAL_PREFIXES
+        };
+        // Value as displayed with the prefix of `level`, once rounded.
+        let shown = |level: usize| match level {
+            0 => value.round(),
+            level => (value / base.powi(level as i32) * 100.0).round() / 100.0,
+        };
+        let mut current = self.fixed.unwrap_or_else(|| level.get());
+        if self.fixed.is_none() {
+            // A value rounding to 1000 or more takes the next prefix, so the
+            // number keeps its width.
+            while current + 1 < prefixes.len()
+                && (value >= base.powi(current as i32 + 1) || shown(current) >= 1000.0)
+            {
+                current += 1;
+            }
+            while current > 0
+                && value < base.powi(current as i32) * (1.0 - self.hysteresis)
+                && shown(current - 1) < 1000.0
+            {
+                current -= 1;
+            }
+            level.set(current);
+        }
+        let width = if self.binary { 2 } else { 1 };
+        let separator = if self.symbol.chars().count() > 1 { " " } else { "" };
+        let number = if current == 0 {
+            format!("{:>6.0}", value)
+        } else {
+            format!("{:>6.2}", value / base.powi(current as i32))
+        };
+        format!(
+            "{} {:>width$}{}{}",
+            number,
+            prefixes[current],
+            separator,
+            self.symbol,
+            width = width
+        )
+    }
+}
+
+/// Formats a count with `,` separating groups of thousands: `12,345`.
+pub(crate) fn thousands(value: u64) -> String {
+    let digits = value.to_string();
+    let mut grouped = String::with_capacity(digits.len() + digits.len() / 3);
+    for (index, digit) in digits.chars().enumerate() {
+        if index > 0 && (digits.len() - index).is_multiple_of(3) {
+            grouped.push(',');
+        }
+        grouped.push(digit);
+    }
+    grouped
+}
+
+/// Counts from which `{pos}` and `{len}` are displayed with a prefix.
+const COMPACT_FROM: u64 = 100_000;
+
+/// Prefix level used to display `value` compactly, `0` for no prefix. The
+/// level is picked once rounded: `999,950` is `1.0M`, not `1000.0k`.
+pub(crate) fn compact_level(value: u64) -> usize {
+    let mut level = 0;
+    if value < COMPACT_FROM {
+        return level;
+    }
+    let shown = |level: usize| (value as f64 / 1000f64.powi(level as i32) * 10.0).round() / 10.0;
+    while level + 1 < DECIMAL_PREFIXES.len() && shown(level) >= 1000.0 {
+        level += 1;
+    }
+    level
+}
+
+/// Formats a count with one decimal and the decimal prefix of `level`, see
+/// [`compact_level`]. `{pos}` and `{len}` share the level of the larger one
+/// so they read alike: `1.2M/8.0M`.
+pub(crate) fn compact(value: u64, level: usize) -> String {
+    match level {
+        0 => value.to_string(),
+        level => format!(
+            "{:.1}{}",
+            value as f64 / 1000f64.powi(level as i32),
+            DECIMAL_PREFIXES[level]
+        ),
+    }
+}
+
+/// Formats a rate of `unit` per second: whole numbers with thousands
+/// separators from ten per second up, two decimals below. Units longer than
+/// five letters are shortened to their first three: `1,043 rec/s`.
+pub(crate) fn unit_rate(per_second: f64, unit: &str) -> String {
+    let short: String = match unit.chars().count() {
+        0..=5 => unit.to_string(),
+        _ => unit.chars().take(3).collect(),
+    };
+    if per_second >= 10.0 {
+        format!("{} {}/s", thousands(per_second.round() as u64), short)
+    } else {
+        format!("{:.2} {}/s", per_second, short)
+    }
+}
+
+/// Duration as it would be said out loud, rounded to its largest unit:
+/// `less than a second`, `1 second`, `2 minutes`, `3 hours`.
+pub(crate) fn spoken(duration: Duration) -> String {
+    let secs = duration.as_secs_f64();
+    let (count, unit) = if secs < 1.0 {
+        return "less than a second".to_string();
+    } else if secs < MINUTE as f64 {
+        (secs.round(), "second")
+    } else if secs < HOUR as f64 {
+        ((secs / MINUTE as f64).round(), "minute")
+    } else {
+        ((secs / HOUR as f64).round(), "hour")
+    };
+    match count as u64 {
+        1 => format!("1 {}", unit),
+        count => format!("{} {}s", count, unit),
+    }
+}
+
+/// Pads or truncates `text` to exactly `width` columns. Over-long text ends
+/// with an ellipsis. Escape sequences take no columns and are all kept, so
+/// colors are reset even when the text they paint is cut.
+pub(crate) fn fit(text: &str, width: usize) -> String {
+    if display_width(text) <= width {
+        let mut fitted = text.to_string();
+        fitted.extend(std::iter::repeat_n(' ', width - display_width(text)));
+        return fitted;
+    }
+    let mut fitted = String::new();
+    let mut used = 0;
+    let mut full = false;
+    for (part, escape) in ansi_parts(text) {
+        if escape {
+            fitted.push_str(part);
+            continue;
+        }
+        for (cluster, columns) in clusters(part) {
+            if full || used + columns + 1 > width {
+                full = true;
+                break;
+            }
+            fitted.push_str(cluster);
+            used += columns;
+        }
+    }
+    if width > 0 {
+        fitted.push('…');
+        used += 1;
+    }
+    fitted.extend(std::iter::repeat_n(' ', width - used));
+    fitted
+}
+
+/// Number of terminal columns used by `text`, colors excluded.
+pub(crate) fn display_width(text: &str) -> usize {
+    clusters(&strip_ansi(text)).map(|(_, columns)| columns).sum()
+}
+
+/// Splits `text` in the clusters of characters displayed as one symbol, with
+/// their width: combining marks and emoji joined by a zero width joiner are
+/// kept with the character they modify.
+fn clusters(text: &str) -> impl Iterator<Item = (&str, usize)> {
+    let mut clusters: Vec<(&str, usize)> = Vec::new();
+    let mut start = 0;
+    let mut width = 0;
+    let mut joined = false;
+    for (index, c) in text.char_indices() {
+        let columns = char_width(c);
+        if index > 0 && columns > 0 && !joined {
+            clusters.push((&text[start..index], width));
+            start = index;
+            width = 0;
+        }
+        if !joined {
+            width += columns;
+        }
+        joined = c == '\u{200D}';
+    }
+    if start < text.len() {
+        clusters.push((&text[start..], width));
+    }
+    clusters.into_iter()
+}
+
+/// Number of terminal columns used by a character: 2 for East Asian wide
+/// characters and emoji, 0 for combining marks and other invisible ones.
+pub(crate) fn char_width(c: char) -> usize {
+    match c {
+        '\u{0}'..='\u{1F}'
+        | '\u{7F}'..='\u{9F}'
+        | '\u{300}'..='\u{36F}'
+        | '\u{1AB0}'..='\u{1AFF}'
+        | '\u{1DC0}'..='\u{1DFF}'
+        | '\u{200B}'..='\u{200F}'
+        | '\u{2028}'..='\u{202E}'
+        | '\u{2060}'..='\u{2064}'
+        | '\u{20D0}'..='\u{20FF}'
+        | '\u{FE00}'..='\u{FE0F}'
+        | '\u{FE20}'..='\u{FE2F}'
+        | '\u{FEFF}'
+        | '\u{1F3FB}'..='\u{1F3FF}'
+        | '\u{E0000}'..='\u{E01EF}' => 0,
+        '\u{1100}'..='\u{115F}'
+        | '\u{231A}'..='\u{231B}'
+        | '\u{23E9}'..='\u{23EC}'
+        | '\u{23F0}'
+        | '\u{23F3}'
+        | '\u{25FD}'..='\u{25FE}'
+        | '\u{2614}'..='\u{2615}'
+        | '\u{2648}'..='\u{2653}'
+        | '\u{26A1}'
+        | '\u{26AA}'..='\u{26AB}'
+        | '\u{26BD}'..='\u{26BE}'
+        | '\u{26C4}'..='\u{26C5}'
+        | '\u{26D4}'
+        | '\u{26EA}'
+        | '\u{26F2}'..='\u{26F5}'
+        | '\u{26FA}'
+        | '\u{26FD}'
+        | '\u{2705}'
+        | '\u{270A}'..='\u{270B}'
+        | '\u{2728}'
+        | '\u{274C}'
+        | '\u{274E}'
+        | '\u{2753}'..='\u{2755}'
+        | '\u{2757}'
+        | '\u{2795}'..='\u{2797}'
+        | '\u{27B0}'
+        | '\u{27BF}'
+        | '\u{2B1B}'..='\u{2B1C}'
+        | '\u{2B50}'
+        | '\u{2B55}'
+        | '\u{2E80}'..='\u{303E}'
+        | '\u{3041}'..='\u{33FF}'
+        | '\u{3400}'..='\u{4DBF}'
+        | '\u{4E00}'..='\u{9FFF}'
+        | '\u{A000}'..='\u{A4CF}'
+        | '\u{A960}'..='\u{A97F}'
+        | '\u{AC00}'..='\u{D7A3}'
+        | '\u{F900}'..='\u{FAFF}'
+        | '\u{FE10}'..='\u{FE19}'
+        | '\u{FE30}'..='\u{FE6F}'
+        | '\u{FF00}'..='\u{FF60}'
+        | '\u{FFE0}'..='\u{FFE6}'
+        | '\u{1F004}'
+        | '\u{1F0CF}'
+        | '\u{1F18E}'
+        | '\u{1F191}'..='\u{1F19A}'
+        | '\u{1F200}'..='\u{1F251}'
+        | '\u{1F300}'..='\u{1F64F}'
+        | '\u{1F680}'..='\u{1F6FF}'
+        | '\u{1F7E0}'..='\u{1F7EB}'
+        | '\u{1F900}'..='\u{1F9FF}'
+        | '\u{1FA70}'..='\u{1FAFF}'
+        | '\u{20000}'..='\u{3FFFD}' => 2,
+        _ => 1,
+    }
+}
+
+/// Column the status words of `println_done` are right-aligned to.
+const STATUS_WIDTH: usize = 12;
+
+/// Line reporting a finished step the way build tools do: the status word in
+/// green, right-aligned, then the message: `    Compiled foo v1.2`.
+pub(crate) fn status_line(status: &str, message: &str) -> String {
+    let padding = STATUS_WIDTH.saturating_sub(display_width(status));
+    format!("{}{} {}", " ".repeat(padding), paint(status, GREEN), message)
+}
+
+/// Paints `text` in red.
+pub(crate) fn red(text: &str) -> String {
+    paint(text, RED)
+}
+
+/// Removes the ANSI escape sequences (colors, cursor moves) from `text`.
+pub(crate) fn strip_ansi(text: &str) -> String {
+    ansi_parts(text)
+        .filter(|(_, escape)| !escape)
+        .map(|(part, _)| part)
+        .collect()
+}
+
+/// Splits `text` in runs of text and ANSI escape sequences, the latter
+/// flagged `true`.
+fn ansi_parts(text: &str) -> impl Iterator<Item = (&str, bool)> {
+    let mut parts = Vec::new();
+    let mut start = 0;
+    let mut chars = text.char_indices();
+    while let Some((index, c)) = chars.next() {
+        if c != '\x1b' {
+            continue;
+        }
+        if start < index {
+            parts.push((&text[start..index], false));
+        }
+        // CSI sequences end with a letter, other escapes are two characters long.
+        let mut end = text.len();
+        if let Some((_, next)) = chars.next() {
+            end = index + 1 + next.len_utf8();
+            if next == '[' {
+                end = text.len();
+                for (index, c) in chars.by_ref() {
+                    if c.is_ascii_alphabetic() {
+                        end = index + 1;
+                        break;
+                    }
+                }
+            }
+        }
+        parts.push((&text[index..end], true));
+        start = end;
+    }
+    if start < text.len() {
+        parts.push((&text[start..], false));
+    }
+    parts.into_iter()
+}
+
+/// Quotes `text` as a JSON string.
+pub(crate) fn json_string(text: &str) -> String {
+    let mut quoted = String::with_capacity(text.len() + 2);
+    quoted.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if (c as u32) < 0x20 => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn secs(secs: u64) -> Duration {
+        Duration::from_secs(secs)
+    }
+
+    #[test]
+    fn durations_roll_over_to_the_next_unit() {
+        let cases = [
+            (59, "00:00:59", "59s"),
+            (60, "00:01:00", "1m 00s"),
+            (3_599, "00:59:59", "59m 59s"),
+            (3_600, "01:00:00", "1h 00m"),
+            (86_399, "23:59:59", "23h 59m"),
+            (86_400, "1d 00:00:00", "1d 00h"),
+        ];
+        for &(duration, hms, human) in cases.iter() {
+            assert_eq!(DurationFormat::Hms.format(secs(duration)), hms);
+            assert_eq!(DurationFormat::HumanShort.format(secs(duration)), human);
+        }
+    }
+
+    #[test]
+    fn adaptive_rolls_over_to_the_next_unit() {
+        let cases = [
+            (59, "59.0s"),
+            (60, "1m00s"),
+            (3_599, "59m59s"),
+            (3_600, "1h00m"),
+            (86_399, "23h59m"),
+            (86_400, "1d00h"),
+        ];
+        for &(duration, adaptive) in cases.iter() {
+            assert_eq!(DurationFormat::Adaptive.format(secs(duration)), adaptive);
+        }
+    }
+
+    #[test]
+    fn adaptive_truncates_below_a_minute() {
+        let adaptive = |millis| DurationFormat::Adaptive.format(Duration::from_millis(millis));
+        assert_eq!(adaptive(0), "0ms");
+        assert_eq!(adaptive(999), "999ms");
+        assert_eq!(adaptive(1_000), "1.0s");
+        assert_eq!(adaptive(59_960), "59.9s");
+        assert_eq!(adaptive(59_999), "59.9s");
+    }
+
+    #[test]
+    fn zero_durations() {
+        assert_eq!(DurationFormat::Hms.format(Duration::ZERO), "00:00:00");
+        assert_eq!(DurationFormat::HumanShort.format(Duration::ZERO), "0s");
+        assert_eq!(DurationFormat::SecondsF64.format(Duration::ZERO), "0.0000 Secs");
+    }
+
+    #[test]
+    fn spoken_rounds_to_the_largest_unit() {
+        assert_eq!(spoken(Duration::from_millis(999)), "less than a second");
+        assert_eq!(spoken(secs(1)), "1 second");
+        assert_eq!(spoken(secs(59)), "59 seconds");
+        assert_eq!(spoken(secs(90)), "2 minutes");
+        assert_eq!(spoken(secs(3_600)), "1 hour");
+    }
+
+    #[test]
+    fn compact_picks_the_prefix_once_rounded() {
+        let shown = |value| compact(value, compact_level(value));
+        assert_eq!(shown(0), "0");
+        assert_eq!(shown(99_999), "99999");
+        assert_eq!(shown(100_000), "100.0k");
+        assert_eq!(shown(999_949), "999.9k");
+        assert_eq!(shown(999_950), "1.0M");
+        assert_eq!(shown(999_949_999), "999.9M");
+        assert_eq!(shown(999_950_000), "1.0G");
+        assert_eq!(shown(u64::MAX), "18446744.1T");
+    }
+
+    #[test]
+    fn units_pick_the_prefix_once_rounded() {
+        assert_eq!(Units::decimal("B").format(0.0), "     0  B");
+        assert_eq!(Units::decimal("B").format(999.4), "   999  B");
+        assert_eq!(Units::decimal("B").format(999.6), "  1.00 kB");
+        assert_eq!(Units::decimal("B").format(999_996.0), "  1.00 MB");
+        assert_eq!(Units::binary("B").format(1_023.6), "  1.00 KiB");
+        assert_eq!(Units::binary("B").format(1_048_570.0), "  1.00 MiB");
+    }
+
+    #[test]
+    fn units_fall_back_below_the_hysteresis() {
+        let units = Units::decimal("B");
+        let level = Cell::new(0);
+        assert_eq!(units.scale(1_500.0, &level), "  1.50 kB");
+        assert_eq!(units.scale(950.0, &level), "  0.95 kB");
+        assert_eq!(units.scale(899.0, &level), "   899  B");
+        assert_eq!(level.get(), 0);
+        // Without hysteresis, a value rounding to the boundary keeps its prefix.
+        let units = Units::decimal("B").with_hysteresis(0.0);
+        let level = Cell::new(2);
+        assert_eq!(units.scale(999_996.0, &level), "  1.00 MB");
+        assert_eq!(units.scale(999_000.0, &level), "999.00 kB");
+    }
+
+    #[test]
+    fn units_survive_non_finite_values() {
+        let units = Units::binary("B");
+        assert!(units.format(f64::NAN).contains("NaN"));
+        assert!(units.format(f64::INFINITY).contains("inf"));
+    }
+
+    #[test]
+    fn thousands_group_by_three() {
+        assert_eq!(thousands(0), "0");
+        assert_eq!(thousands(999), "999");
+        assert_eq!(thousands(1_000), "1,000");
+        assert_eq!(thousands(1_234_567), "1,234,567");
+    }
+
+    #[test]
+    fn unit_rates() {
+        assert_eq!(unit_rate(0.0, "it"), "0.00 it/s");
+        assert_eq!(unit_rate(0.5, "it"), "0.50 it/s");
+        assert_eq!(unit_rate(10.0, "it"), "10 it/s");
+        assert_eq!(unit_rate(1_043.4, "records"), "1,043 rec/s");
+    }
+
+    #[test]
+    fn fit_pads_or_truncates() {
+        assert_eq!(fit("hello", 8), "hello   ");
+        assert_eq!(fit("hello", 5), "hello");
+        assert_eq!(fit("hello world", 6), "hello…");
+        assert_eq!(fit("hello", 0), "");
+        assert_eq!(fit("日本語", 4), "日… ");
+    }
+
+    #[test]
+    fn fit_keeps_escape_sequences() {
+        assert_eq!(fit("\x1b[31mfailed\x1b[0m items", 5), "\x1b[31mfail\x1b[0m…");
+        assert_eq!(fit("\x1b[31mok\x1b[0m", 4), "\x1b[31mok\x1b[0m  ");
+    }
+
+    #[test]
+    fn strip_ansi_keeps_the_text() {
+        assert_eq!(strip_ansi("\x1b[32mok\x1b[0m"), "ok");
+        assert_eq!(strip_ansi("\x1b[1A\x1b[2Kline"), "line");
+        assert_eq!(strip_ansi("plain"), "plain");
+    }
+
+    #[test]
+    fn colors_take_no_columns() {
+        assert_eq!(display_width("\x1b[32mok\x1b[0m"), 2);
+        assert_eq!(display_width("日本"), 4);
+        assert_eq!(display_width("e\u{301}"), 1);
+    }
+
+    #[test]
+    fn status_lines_align_the_status() {
+        assert_eq!(strip_ansi(&status_line("Compiled", "foo")), "    Compiled foo");
+        assert_eq!(strip_ansi(&status_line("Decompressing", "foo")), "Decompressing foo");
+    }
+
+    #[test]
+    fn json_strings_are_escaped() {
+        assert_eq!(json_string("a \"b\"\n\\"), "\"a \\\"b\\\"\\n\\\\\"");
+        assert_eq!(json_string("\u{1}"), "\"\\u0001\"");
+    }
+}
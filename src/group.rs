@@ -0,0 +1,159 @@
+//!
+//! Groups of bars with an aggregate bar tracking their combined completion.
+//!
+use crate::ext::{Observer, ProgressState};
+use crate::handle::ProgressHandle;
+use crate::multi::MultiProgress;
+use crate::state::{lock, BarState, MAX_COLUMN_WIDTH};
+use crate::template::Template;
+use crate::ProgressBar;
+use std::sync::{Arc, Mutex};
+
+/// Resolution of the overall bar: its position is in thousandths.
+const OVERALL_LENGTH: u64 = 1000;
+
+/// Template of the overall bar, its message defaults to `overall`.
+const OVERALL_TEMPLATE: &str = "{percent}% {bar} {msg} {elapsed}";
+
+/// Several task bars drawn below an overall bar reflecting their combined
+/// completion. Each task contributes in proportion to its weight, so a task
+/// expected to take twice as long can count twice as much.
+///
+/// Unbounded tasks count as not started until they finish. The overall bar
+/// finishes once every task added so far is finished.
+///
+/// # Example
+/// ```
+/// use cpbar::*;
+///
+/// let group = BarGroup::new();
+/// let download = group.add(ProgressBar::new(0..40).with_bounds(), 3);
+/// let checksum = group.add(ProgressBar::new(0..10).with_bounds(), 1);
+/// std::thread::scope(|scope| {
+///     scope.spawn(move || for _ in download {});
+///     scope.spawn(move || for _ in checksum {});
+/// });
+/// assert_eq!(group.overall().percent(), Some(100.0));
+/// ```
+#[derive(Clone)]
+pub struct BarGroup {
+    multi: MultiProgress,
+    overall: Arc<Mutex<BarState>>,
+    tasks: Arc<Mutex<Vec<Task>>>,
+}
+
+/// Contribution of a task to the overall bar.
+struct Task {
+    weight: u64,
+    done: f64,
+    finished: bool,
+}
+
+impl Default for BarGroup {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BarGroup {
+    /// Creates an empty group, with its overall bar on the first line.
+    pub fn new() -> Self {
+        let mut overall = BarState::new();
+        overall.set_length(OVERALL_LENGTH, MAX_COLUMN_WIDTH);
+        overall.message = "overall".to_string();
+        overall.template = Some(Template::parse(OVERALL_TEMPLATE));
+        let overall = overall.shared();
+        let multi = MultiProgress::new();
+        multi.attach(&overall);
+        Self {
+            multi,
+            overall,
+            tasks: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+
+    /// Adds a task bar below the previous ones, contributing `weight` to the
+    /// overall completion.
+    pub fn add<Iter, Bound>(
+        &self,
+        bar: ProgressBar<Iter, Bound>,
+        weight: u64,
+    ) -> ProgressBar<Iter, Bound> {
+        let index = {
+            let mut tasks = lock(&self.tasks);
+            tasks.push(Task {
+                weight,
+                done: 0.0,
+                finished: false,
+            });
+            tasks.len() - 1
+        };
+        let bar = self.multi.add(bar);
+        lock(&bar.state).observers.push(Box::new(Report {
+            index,
+            tasks: Arc::clone(&self.tasks),
+            overall: Arc::clone(&self.overall),
+        }));
+        bar
+    }
+
+    /// Handle to the overall bar, for instance to change its message.
+    pub fn overall(&self) -> ProgressHandle {
+        ProgressHandle::new(Arc::clone(&self.overall))
+    }
+
+    /// The display the bars of the group are drawn on.
+    pub fn multi(&self) -> &MultiProgress {
+        &self.multi
+    }
+}
+
+/// Observer of a task bar updating the overall bar.
+struct Report {
+    index: usize,
+    tasks: Arc<Mutex<Vec<Task>>>,
+    overall: Arc<Mutex<BarState>>,
+}
+
+impl Report {
+    fn update(&self, done: f64, finished: bool) {
+        let (position, all_finished) = {
+            let mut tasks = lock(&self.tasks);
+            tasks[self.index].done = done;
+            tasks[self.index].finished = finished;
+            let total: u64 = tasks.iter().map(|task| task.weight).sum();
+            let weighted: f64 = tasks
+                .iter()
+                .map(|task| task.weight as f64 * task.done)
+                .sum();
+            let position = match total {
+                0 => OVERALL_LENGTH,
+                total => (weighted * OVERALL_LENGTH as f64 / total as f64).round() as u64,
+            };
+            (position, tasks.iter().all(|task| task.finished))
+        };
+        let mut overall = lock(&self.overall);
+        overall.position = position.min(OVERALL_LENGTH);
+        if all_finished {
+            overall.position = OVERALL_LENGTH;
+            overall.finish();
+        } else {
+            overall.draw();
+        }
+    }
+}
+
+impl Observer for Report {
+    fn on_tick(&mut self, state: &ProgressState) {
+        let done = match state.length {
+            Some(0) => 1.0,
+            Some(length) => state.position.min(length) as f64 / length as f64,
+            None => 0.0,
+        };
+        self.update(done, false);
+    }
+
+    fn on_finish(&mut self, _state: &ProgressState) {
+        self.update(1.0, true);
+    }
+}
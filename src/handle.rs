@@ -0,0 +1,273 @@
+//!
+//! Handles used to interact with a progress bar while it is being iterated.
+//!
+use crate::draw;
+use crate::ext::ProgressState;
+use crate::format::status_line;
+use crate::multi::BarId;
+use crate::segment::SegmentId;
+use crate::task::{Phase, Task};
+use crate::state::{lock, BarState};
+use crate::writer::ProgressWriter;
+use std::io;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, TryLockError};
+
+/// Cheap, cloneable handle to a progress bar. The bar itself is consumed by the
+/// loop iterating it, handles remain usable from the loop body or other threads.
+///
+/// # Example
+/// ```
+/// use cpbar::*;
+///
+/// let crates = vec!["serde", "rand", "log"];
+/// let progress_bar = ProgressBar::new(crates.iter()).with_bounds();
+/// let handle = progress_bar.handle();
+/// for name in progress_bar {
+///     // compile crate...
+///     handle.println_done("Compiled", name);
+/// }
+/// ```
+#[derive(Clone)]
+pub struct ProgressHandle {
+    pub(crate) state: Arc<Mutex<BarState>>,
+    counter: Arc<AtomicU64>,
+}
+
+impl ProgressHandle {
+    pub(crate) fn new(state: Arc<Mutex<BarState>>) -> Self {
+        let counter = Arc::clone(&lock(&state).concurrent);
+        Self { state, counter }
+    }
+
+    /// Stable identifier of the bar.
+    pub fn id(&self) -> BarId {
+        lock(&self.state).id
+    }
+
+    /// Progress made so far, see [`ProgressBar::position`](crate::ProgressBar::position).
+    pub fn position(&self) -> u64 {
+        lock(&self.state).current_position()
+    }
+
+    /// Total the bar is progressing towards, `None` for unbounded bars.
+    pub fn length(&self) -> Option<u64> {
+        lock(&self.state).length
+    }
+
+    /// Time since the bar started.
+    pub fn elapsed(&self) -> std::time::Duration {
+        lock(&self.state).elapsed()
+    }
+
+    /// Completion of a bounded bar between 0 and 100, `None` for unbounded bars.
+    pub fn percent(&self) -> Option<f64> {
+        lock(&self.state).percent()
+    }
+
+    /// Copy of the current state of the bar, see
+    /// [`ProgressBar::snapshot`](crate::ProgressBar::snapshot).
+    pub fn snapshot(&self) -> ProgressState {
+        lock(&self.state).snapshot()
+    }
+
+    /// Saves the progress made so far to `path`, see
+    /// [`ProgressBar::resume_from`](crate::ProgressBar::resume_from).
+    pub fn save_checkpoint<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let checkpoint = lock(&self.state).checkpoint();
+        checkpoint.save(path.as_ref())
+    }
+
+    /// Prints a line above the bar without corrupting it, the bar is redrawn
+    /// below the message. Use it instead of `println!` inside the loop body.
+    pub fn println(&self, message: &str) {
+        lock(&self.state).target.println(message);
+    }
+
+    /// Erases the bar, runs `f` and redraws the bar below whatever `f` printed,
+    /// so application output can coexist with the progress display. `f` can
+    /// update the bar, its new state is drawn once `f` returns.
+    ///
+    /// # Example
+    /// ```
+    /// use cpbar::*;
+    /// let progress_bar = ProgressBar::new((0..3)).with_bounds();
+    /// let handle = progress_bar.handle();
+    /// for i in progress_bar {
+    ///     let squared = handle.suspend(|| {
+    ///         eprintln!("processing {}", i);
+    ///         handle.set_message(&format!("squared {}", i * i));
+    ///         handle.println("done");
+    ///         i * i
+    ///     });
+    /// }
+    /// ```
+    pub fn suspend<R>(&self, f: impl FnOnce() -> R) -> R {
+        draw::suspend(&self.state, f)
+    }
+
+    /// Wraps the writer of a logger so its lines are printed through
+    /// [`Self::suspend`], see [`ProgressWriter`].
+    pub fn writer<W: std::io::Write>(&self, inner: W) -> ProgressWriter<W> {
+        ProgressWriter::for_bar(self.clone(), inner)
+    }
+
+    /// Wraps a logger of the `log` crate so its records are written through
+    /// [`Self::suspend`], see [`ProgressLogger`](crate::logger::ProgressLogger).
+    #[cfg(feature = "log")]
+    pub fn logger<L: log::Log>(&self, inner: L) -> crate::logger::ProgressLogger<L> {
+        crate::logger::ProgressLogger::new(crate::writer::Target::Bar(self.clone()), inner)
+    }
+
+    /// Prints a finished step above the bar the way build tools report them:
+    /// `status` in green, right-aligned to twelve columns, then `message`.
+    /// The bar stays live below the growing list of steps. Use
+    /// [`Self::println`] for lines printed as they are.
+    ///
+    /// # Example
+    /// ```
+    /// use cpbar::*;
+    ///
+    /// let capture = testing::Capture::new();
+    /// let progress_bar = ProgressBar::new(["serde", "rand"].iter())
+    ///     .with_bounds()
+    ///     .with_draw_target(capture.clone());
+    /// let handle = progress_bar.handle();
+    /// for name in progress_bar {
+    ///     handle.println_done("Compiled", name);
+    /// }
+    /// assert_eq!(capture.printed(), ["    Compiled serde", "    Compiled rand"]);
+    /// ```
+    pub fn println_done(&self, status: &str, message: &str) {
+        lock(&self.state)
+            .target
+            .println(&status_line(status, message));
+    }
+
+    /// Counts the current item as skipped in the summary records.
+    pub fn mark_skipped(&self) {
+        lock(&self.state).skipped += 1;
+    }
+
+    /// Counts the current item in the given segment of a segmented bar, see
+    /// [`ProgressBar::with_segments`](crate::ProgressBar::with_segments). The
+    /// position is unchanged, the count shows from the next frame.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the bar has no segment at this index.
+    pub fn inc_segment(&self, segment: SegmentId) {
+        let mut state = lock(&self.state);
+        let segments = state.segments.len();
+        match state.segments.get_mut(segment.0) {
+            Some((_, count)) => *count += 1,
+            None => panic!("segment {} out of range, the bar has {}", segment.0, segments),
+        }
+    }
+
+    /// Number of items counted in the given segment, `0` if there is none.
+    pub fn segment(&self, segment: SegmentId) -> u64 {
+        lock(&self.state)
+            .segments
+            .get(segment.0)
+            .map_or(0, |(_, count)| *count)
+    }
+
+    /// Starts a timed phase of the work, see
+    /// [`ProgressBar::task`](crate::ProgressBar::task).
+    pub fn task(&self, name: &str) -> Task {
+        Task::start(&self.state, name)
+    }
+
+    /// Time spent in the tasks that ended, see
+    /// [`ProgressBar::phases`](crate::ProgressBar::phases).
+    pub fn phases(&self) -> Vec<Phase> {
+        lock(&self.state).phases.clone()
+    }
+
+    /// Advances the spinner and redraws the bar without moving it, see
+    /// [`ProgressBar::with_heartbeat`](crate::ProgressBar::with_heartbeat).
+    pub fn tick(&self) {
+        let mut state = lock(&self.state);
+        if !state.finished {
+            state.heartbeat();
+        }
+    }
+
+    /// Starts the bar over for another pass, see
+    /// [`ProgressBar::reset`](crate::ProgressBar::reset).
+    pub fn reset(&self) {
+        lock(&self.state).reset();
+    }
+
+    /// Restarts the rate and ETA estimations, see
+    /// [`ProgressBar::reset_eta`](crate::ProgressBar::reset_eta).
+    pub fn reset_eta(&self) {
+        lock(&self.state).reset_eta();
+    }
+
+    /// Changes the total of the bar, see
+    /// [`ProgressBar::set_length`](crate::ProgressBar::set_length).
+    pub fn set_length(&self, length: u64) {
+        lock(&self.state).change_length(length);
+    }
+
+    /// Hides or shows the bar at runtime, see
+    /// [`ProgressBar::set_hidden`](crate::ProgressBar::set_hidden).
+    pub fn set_hidden(&self, hidden: bool) {
+        lock(&self.state).hidden = hidden;
+    }
+
+    /// Replaces the message displayed by the `{msg}` template field.
+    pub fn set_message(&self, message: &str) {
+        let mut state = lock(&self.state);
+        state.message = message.to_string();
+        if state.index > 0 && !state.finished {
+            state.draw();
+        }
+    }
+
+    /// Advances the bar by `delta`, for work spread over several threads
+    /// instead of driven by iterating the bar. The counter is atomic and the
+    /// frame is only drawn by whichever thread finds the bar idle, so workers
+    /// never wait on each other to report progress.
+    ///
+    /// # Example
+    /// ```
+    /// use cpbar::*;
+    ///
+    /// let files: Vec<u32> = (0..64).collect();
+    /// let progress_bar = ProgressBar::new(files.iter()).with_bounds();
+    /// let handle = progress_bar.handle();
+    /// std::thread::scope(|scope| {
+    ///     for chunk in files.chunks(16) {
+    ///         let handle = handle.clone();
+    ///         scope.spawn(move || {
+    ///             for _file in chunk {
+    ///                 // process file...
+    ///                 handle.inc(1);
+    ///             }
+    ///         });
+    ///     }
+    /// });
+    /// handle.finish();
+    /// ```
+    pub fn inc(&self, delta: u64) {
+        self.counter.fetch_add(delta, Ordering::Relaxed);
+        let mut state = match self.state.try_lock() {
+            Ok(state) => state,
+            Err(TryLockError::Poisoned(poisoned)) => poisoned.into_inner(),
+            Err(TryLockError::WouldBlock) => return,
+        };
+        if !state.finished {
+            state.tick(0);
+        }
+    }
+
+    /// Finishes the bar, counting any progress reported by [`Self::inc`]
+    /// that was not drawn yet.
+    pub fn finish(&self) {
+        lock(&self.state).finish();
+    }
+}
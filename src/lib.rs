@@ -34,89 +34,1578 @@
 //! }
 //! ```
 //!
-#[allow(dead_code)]
-#[warn(missing_docs)]
+//! The line layout can be changed through a template and durations can be displayed
+//! using one of the [`DurationFormat`] presets:
+//!
+//! ```
+//! use cpbar::*;
+//!
+//! let vector = vec![1,2,4,5,6,6,7,8,9,0];
+//! for element in ProgressBar::new(vector.iter())
+//!     .with_bounds()
+//!     .with_duration_format(DurationFormat::Hms)
+//!     .with_template("{bar} {percent}% elapsed {elapsed} eta {eta}")
+//! {
+//!     // execute operation with elements
+//! }
+//! ```
+//!
+#![warn(missing_docs)]
 
-/// Constant used to clear screen line on console when printing
-#[doc(hidden)]
-const CLEAR: &str = "\x1b[0J\x1b[1A";
-const MAX_COLUMN_WIDTH: usize = 30;
+#[cfg(feature = "bench")]
+pub mod bench;
+#[cfg(feature = "async")]
+pub mod stream;
+#[cfg(feature = "rayon")]
+pub mod par;
+#[cfg(feature = "log")]
+pub mod logger;
+#[cfg(all(feature = "signals", unix))]
+pub mod signal;
+pub mod ext;
+pub mod testing;
+mod chain;
+mod checkpoint;
+mod draw;
+mod error;
+mod format;
+mod group;
+mod handle;
+mod milestone;
+mod multi;
+mod os;
+mod prefetch;
+mod rate;
+mod segment;
+mod sink;
+mod state;
+mod stats;
+mod styles;
+mod template;
+mod term;
+mod task;
+mod tick;
+mod timed;
+mod writer;
+
+pub use chain::Chain;
+pub use draw::RedrawMode;
+pub use error::ConfigError;
+pub use format::{DurationFormat, Units};
+pub use group::BarGroup;
+pub use handle::ProgressHandle;
+pub use multi::{BarId, MultiProgress};
+pub use os::OsProgress;
+pub use prefetch::Prefetch;
+pub use rate::{RateEstimator, RateTracker};
+pub use segment::{Segment, SegmentId};
+pub use stats::{SessionStats, Stats};
+pub use styles::{BarStyle, Preset};
+pub use task::{Phase, Task};
+pub use timed::Deadline;
+pub use writer::ProgressWriter;
+use sink::Mirror;
+use state::{lock, try_lock, BarState, Estimator, ScaledUnits, MAX_COLUMN_WIDTH};
+use checkpoint::Checkpoint;
+use format::status_line;
+use milestone::{Milestones, Notifier, PLAIN_STEP};
+use os::OsIndicator;
+use std::fmt;
+use std::io;
+use std::iter::{FusedIterator, Skip};
+use std::path::Path;
+use std::sync::{Arc, Mutex, Weak};
+use template::{Template, HEARTBEAT_TEMPLATE};
+use tick::Ticker;
 
 /// ProgressBar bar structure. Crates a progress bar from an iterable element given.
 /// Displays the progress as items on the iterator are consumed.
 pub struct ProgressBar<Iter, Bound> {
+    /// Declared first so it runs while the bar still holds its state.
+    guard: DropGuard,
     iter: Iter,
-    index: usize,
-    start: std::time::Instant,
     bound: Bound,
+    state: Arc<Mutex<BarState>>,
+    ticker: Option<Ticker>,
+    /// Whether an item was requested from an asynchronous stream and not received yet.
+    polling: bool,
+    batch: Batch,
+}
+
+/// Restores the terminal when a bar is dropped before finishing, because the
+/// loop body panicked or broke out of the loop. A bar dropped while handles
+/// can still drive it is left alone, unless the thread is panicking.
+struct DropGuard(Weak<Mutex<BarState>>);
+
+impl Drop for DropGuard {
+    fn drop(&mut self) {
+        if !std::thread::panicking() && self.0.strong_count() > 1 {
+            return;
+        }
+        if let Some(state) = self.0.upgrade() {
+            // The panicking thread may hold the lock, in `suspend` for instance.
+            if let Some(mut state) = try_lock(&state) {
+                state.abandon();
+            }
+        }
+    }
+}
+
+/// Items handed out without touching the shared state, see `with_batch`.
+#[derive(Default)]
+struct Batch {
+    size: usize,
+    count: usize,
+    weight: u64,
+    errors: u64,
+}
+
+/// Unbounded iterator type state. This is used by the internal API
+/// in order to accept unbounded iterators.
+#[doc(hidden)]
+pub struct Unbounded;
+
+/// Bounded iterator type state. This is used by the internal API
+/// in order to accept bounded iterators.
+#[doc(hidden)]
+pub struct Bounded;
+
+/// Weighted iterator type state. Each item advances the bar by its own weight.
+#[doc(hidden)]
+pub struct Weighted<F> {
+    weigh: F,
+}
+
+/// Estimated iterator type state. The total is revised while iterating.
+#[doc(hidden)]
+pub struct Estimated;
+
+/// Timed type state. The bar fills with time, items do not advance it.
+#[doc(hidden)]
+pub struct Timed;
+
+/// Trait for internal usage. Type states drawing a bar towards a total.
+#[doc(hidden)]
+pub trait ProgressBarBounded {}
+
+impl ProgressBarBounded for Bounded {}
+
+impl<F> ProgressBarBounded for Weighted<F> {}
+
+impl ProgressBarBounded for Estimated {}
+
+impl ProgressBarBounded for Timed {}
+
+impl<Bound: ProgressBarBounded> ProgressBarBounded for Fallible<Bound> {}
+
+/// Fallible iterator type state wrapping another one. Counts the `Err` items.
+#[doc(hidden)]
+pub struct Fallible<Bound> {
+    inner: Bound,
+}
+
+/// Trait for internal usage. Weight of each item consumed by the bar.
+#[doc(hidden)]
+pub trait ProgressBarWeight<Item> {
+    /// Amount by which the item advances the bar.
+    fn weight(&self, _item: &Item) -> u64 {
+        1
+    }
+
+    /// Whether the item counts as a failure.
+    fn failed(&self, _item: &Item) -> bool {
+        false
+    }
+}
+
+impl<Item> ProgressBarWeight<Item> for Unbounded {}
+
+impl<Item> ProgressBarWeight<Item> for Bounded {}
+
+impl<Item> ProgressBarWeight<Item> for Estimated {}
+
+impl<Item> ProgressBarWeight<Item> for Timed {
+    fn weight(&self, _item: &Item) -> u64 {
+        0
+    }
+}
+
+impl<Item, F> ProgressBarWeight<Item> for Weighted<F>
+where
+    F: Fn(&Item) -> u64,
+{
+    fn weight(&self, item: &Item) -> u64 {
+        (self.weigh)(item)
+    }
+}
+
+impl<T, E, Bound> ProgressBarWeight<Result<T, E>> for Fallible<Bound>
+where
+    Bound: ProgressBarWeight<Result<T, E>>,
+{
+    fn weight(&self, item: &Result<T, E>) -> u64 {
+        self.inner.weight(item)
+    }
+
+    fn failed(&self, item: &Result<T, E>) -> bool {
+        item.is_err()
+    }
 }
 
-/// Trait for internal usage. Used to print the progess of each entry.
-#[doc(hidden)]
-pub trait ProgressBarDisplay
-where
-    Self: Sized,
-{
-    fn display<Iter>(&self, progress: &ProgressBar<Iter, Self>);
-}
+impl<Iter, Bound> ProgressBar<Iter, Bound> {
+    /// Moves the bar to another type state.
+    fn with_state<B>(self, bound: B) -> ProgressBar<Iter, B> {
+        ProgressBar {
+            guard: self.guard,
+            iter: self.iter,
+            bound,
+            state: self.state,
+            ticker: self.ticker,
+            polling: self.polling,
+            batch: self.batch,
+        }
+    }
+
+    /// Called when a new item is requested: the item handed out on the
+    /// previous call is now processed.
+    fn request(&mut self) {
+        let mut state = lock(&self.state);
+        let advance = std::mem::take(&mut state.pending) + std::mem::take(&mut self.batch.weight);
+        state.errors += std::mem::take(&mut self.batch.errors);
+        self.batch.count = 0;
+        state.tick(advance);
+    }
+
+    /// Called with the item obtained from the inner iterator, `None` once exhausted.
+    fn receive<Item>(&mut self, item: Option<&Item>)
+    where
+        Bound: ProgressBarWeight<Item>,
+    {
+        match item {
+            Some(item) => {
+                let mut state = lock(&self.state);
+                state.pending = self.bound.weight(item);
+                if self.bound.failed(item) {
+                    state.errors += 1;
+                }
+            }
+            None => {
+                lock(&self.state).finish();
+                if let Some(mut ticker) = self.ticker.take() {
+                    ticker.stop();
+                }
+            }
+        }
+    }
+
+    /// Validates the configuration of the bar, reporting the settings that
+    /// cannot work instead of drawing nonsense: an empty charset, a zero
+    /// width, a smoothing factor or rate estimator out of range, a zero
+    /// sample limit or a zero steady tick interval.
+    ///
+    /// # Example
+    /// ```
+    /// use cpbar::*;
+    ///
+    /// let progress_bar = ProgressBar::new((0..6))
+    ///     .with_bounds()
+    ///     .with_style(BarStyle::new(""))
+    ///     .try_build();
+    /// assert_eq!(progress_bar.unwrap_err(), ConfigError::EmptyCharset);
+    ///
+    /// // Empty sequences are valid, and render as complete.
+    /// let mut empty = ProgressBar::new((0..0)).with_bounds().try_build()?;
+    /// assert_eq!(empty.next(), None);
+    /// assert_eq!(empty.percent(), Some(100.0));
+    /// assert!(empty.to_string().starts_with("100% []"));
+    ///
+    /// let endless = ProgressBar::new((0..6))
+    ///     .with_rate_estimator(RateEstimator::SlidingWindow { secs: f64::INFINITY })
+    ///     .try_build();
+    /// assert!(matches!(endless, Err(ConfigError::InvalidRateEstimator(_))));
+    ///
+    /// let busy = ProgressBar::new((0..6))
+    ///     .enable_steady_tick(std::time::Duration::ZERO)
+    ///     .try_build();
+    /// assert_eq!(busy.unwrap_err(), ConfigError::ZeroTickInterval);
+    /// # Ok::<(), ConfigError>(())
+    /// ```
+    ///
+    pub fn try_build(self) -> Result<Self, ConfigError> {
+        lock(&self.state).validate()?;
+        if let Some(ticker) = &self.ticker {
+            if ticker.interval().is_zero() {
+                return Err(ConfigError::ZeroTickInterval);
+            }
+        }
+        Ok(self)
+    }
+
+    /// Returns a copy of the current state of the bar, which can be sent to
+    /// another thread or serialized with [`ext::ProgressState::to_json`], or
+    /// with serde when the `serde` feature is enabled.
+    ///
+    /// # Example
+    /// ```
+    /// use cpbar::*;
+    /// let mut progress_bar = ProgressBar::new((0..6)).with_bounds();
+    /// progress_bar.by_ref().take(4).for_each(drop);
+    /// let snapshot = progress_bar.snapshot();
+    /// assert_eq!(snapshot.position, 3);
+    /// assert_eq!(snapshot.length, Some(6));
+    /// assert!(!snapshot.finished);
+    /// ```
+    ///
+    pub fn snapshot(&self) -> ext::ProgressState {
+        lock(&self.state).snapshot()
+    }
+
+    /// Returns the timing statistics of the items processed so far.
+    ///
+    /// # Example
+    /// ```
+    /// use cpbar::*;
+    /// let mut progress_bar = ProgressBar::new((0..6)).with_bounds();
+    /// for _ in progress_bar.by_ref() {
+    ///     std::thread::sleep(std::time::Duration::from_millis(10));
+    /// }
+    /// let stats = progress_bar.stats();
+    /// assert_eq!(stats.count, 6);
+    /// assert!(stats.min >= std::time::Duration::from_millis(10));
+    /// assert!(stats.min <= stats.mean && stats.mean <= stats.max);
+    /// assert!(stats.p95 <= stats.max);
+    /// ```
+    ///
+    pub fn stats(&self) -> Stats {
+        lock(&self.state).stats()
+    }
+
+    /// Progress made so far: the number of items processed, or the sum of
+    /// their weights. The item currently handed out by the loop is not
+    /// counted until the next one is requested.
+    ///
+    /// # Example
+    /// ```
+    /// use cpbar::*;
+    /// let mut progress_bar = ProgressBar::new((0..4)).with_bounds();
+    /// progress_bar.next();
+    /// progress_bar.next();
+    /// assert_eq!(progress_bar.position(), 1);
+    /// assert_eq!(progress_bar.length(), Some(4));
+    /// assert_eq!(progress_bar.percent(), Some(25.0));
+    /// progress_bar.by_ref().for_each(drop);
+    /// assert_eq!(progress_bar.position(), 4);
+    /// ```
+    ///
+    pub fn position(&self) -> u64 {
+        lock(&self.state).current_position()
+    }
+
+    /// Total the bar is progressing towards, `None` for unbounded bars.
+    pub fn length(&self) -> Option<u64> {
+        lock(&self.state).length
+    }
+
+    /// Time since the bar started.
+    pub fn elapsed(&self) -> std::time::Duration {
+        lock(&self.state).elapsed()
+    }
+
+    /// Completion of a bounded bar between 0 and 100, `None` for unbounded bars.
+    pub fn percent(&self) -> Option<f64> {
+        lock(&self.state).percent()
+    }
+
+    /// Saves the position, total and elapsed time of the bar to `path` so an
+    /// interrupted job can continue where it left off with
+    /// [`ProgressBar::resume_from`]. The item being processed counts as done:
+    /// save at the end of the loop body, through [`ProgressHandle::save_checkpoint`].
+    pub fn save_checkpoint<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let checkpoint = lock(&self.state).checkpoint();
+        checkpoint.save(path.as_ref())
+    }
+
+    /// Starts the bar over for another pass of a multi-pass algorithm: the
+    /// position goes back to zero, the clock restarts and the rate and ETA
+    /// estimations start afresh. The look and the total are kept, and a
+    /// finished bar is redrawn on its previous line instead of below it.
+    ///
+    /// # Example
+    /// ```
+    /// use cpbar::*;
+    /// let data = vec![3, 1, 2];
+    /// let progress_bar = ProgressBar::new(data.iter()).with_bounds();
+    /// let handle = progress_bar.handle();
+    /// for pass in 0..3 {
+    ///     progress_bar.reset();
+    ///     for _ in &data {
+    ///         handle.inc(1);
+    ///     }
+    /// }
+    /// handle.finish();
+    /// assert_eq!(progress_bar.position(), 3);
+    /// ```
+    ///
+    pub fn reset(&self) {
+        lock(&self.state).reset();
+    }
+
+    /// Forgets the speed measured so far, so the rate and the ETA only
+    /// account for the progress made from now on. Useful when a phase of the
+    /// work runs at a different speed than the previous one.
+    ///
+    /// # Example
+    /// ```
+    /// use cpbar::*;
+    /// let mut progress_bar = ProgressBar::new((0..100)).with_bounds();
+    /// // a slow warm-up...
+    /// progress_bar.by_ref().take(10).for_each(drop);
+    /// progress_bar.reset_eta();
+    /// assert_eq!(progress_bar.position(), 9);
+    /// ```
+    ///
+    pub fn reset_eta(&self) {
+        lock(&self.state).reset_eta();
+    }
+
+    /// Changes the total of the bar, for instance between two passes that do
+    /// not process the same number of items. The bar becomes exactly bounded.
+    ///
+    /// # Example
+    /// ```
+    /// use cpbar::*;
+    /// let progress_bar = ProgressBar::new((0..6)).with_bounds();
+    /// progress_bar.set_length(4);
+    /// assert_eq!(progress_bar.length(), Some(4));
+    /// ```
+    ///
+    pub fn set_length(&self, length: u64) {
+        lock(&self.state).change_length(length);
+    }
+
+    /// Hides or shows the bar. A hidden bar still consumes its iterator but
+    /// renders nothing, not even the frames it would format, so `--quiet`
+    /// flags can keep the same code path at no cost. Bars start hidden when
+    /// the `CPBAR_HIDDEN` or `NO_PROGRESS` environment variable is set to
+    /// anything but an empty string or `0`.
+    ///
+    /// # Example
+    /// ```
+    /// use cpbar::*;
+    /// let quiet = std::env::args().any(|arg| arg == "--quiet");
+    /// let progress_bar = ProgressBar::new((0..6)).with_bounds();
+    /// progress_bar.set_hidden(quiet);
+    /// assert_eq!(progress_bar.sum::<i32>(), 15);
+    /// ```
+    ///
+    pub fn set_hidden(&self, hidden: bool) {
+        lock(&self.state).hidden = hidden;
+    }
+
+    /// Bounds the memory used by the timing statistics: at most `limit` item
+    /// times are kept, chosen by reservoir sampling. Minimum, maximum and mean
+    /// stay exact while the 95th percentile becomes an estimate.
+    ///
+    /// # Example
+    /// ```
+    /// use cpbar::*;
+    /// let progress_bar = ProgressBar::new((0..1_000_000)).with_sample_limit(1024);
+    /// ```
+    ///
+    pub fn with_sample_limit(self, limit: usize) -> Self {
+        lock(&self.state).recorder.set_limit(limit);
+        self
+    }
+
+    /// Prints a one line summary of the item timing statistics when the bar finishes,
+    /// followed by the total time spent in each phase when [`Self::task`] was used.
+    ///
+    /// # Example
+    /// ```
+    /// use cpbar::*;
+    /// for _ in ProgressBar::new((0..6)).with_bounds().with_summary() {
+    ///     // 6 items: min 2ms / max 5ms / mean ...
+    /// }
+    /// ```
+    ///
+    pub fn with_summary(self) -> Self {
+        lock(&self.state).summary = true;
+        self
+    }
+
+    /// Writes a single machine-readable JSON record to `sink` when the bar
+    /// finishes, so orchestration systems can ingest the outcome of a job
+    /// without parsing the console output. Durations are in seconds, `total`
+    /// is `null` for unbounded bars.
+    ///
+    /// # Example
+    /// ```
+    /// use cpbar::*;
+    /// # use std::io::Write;
+    /// # use std::sync::{Arc, Mutex};
+    /// # #[derive(Clone, Default)]
+    /// # struct Shared(Arc<Mutex<Vec<u8>>>);
+    /// # impl Write for Shared {
+    /// #     fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+    /// #         self.0.lock().unwrap().write(buf)
+    /// #     }
+    /// #     fn flush(&mut self) -> std::io::Result<()> {
+    /// #         Ok(())
+    /// #     }
+    /// # }
+    /// let sink = Shared::default();
+    /// let results = vec![Ok(1), Err("corrupted"), Ok(3), Ok(4)];
+    /// let progress_bar = ProgressBar::new(results.into_iter())
+    ///                 .with_bounds()
+    ///                 .with_json_summary(sink.clone())
+    ///                 .try_progress();
+    /// let handle = progress_bar.handle();
+    /// for result in progress_bar {
+    ///     if result == Ok(4) {
+    ///         handle.mark_skipped();
+    ///     }
+    /// }
+    /// let record = String::from_utf8(sink.0.lock().unwrap().clone()).unwrap();
+    /// assert!(record.starts_with("{\"id\":"));
+    /// assert!(record.contains("\"items\":4,\"position\":4,\"total\":4,"));
+    /// assert!(record.ends_with("\"failures\":1,\"skipped\":1,\"message\":\"\"}\n"));
+    /// ```
+    ///
+    pub fn with_json_summary<W>(self, sink: W) -> Self
+    where
+        W: std::io::Write + Send + 'static,
+    {
+        lock(&self.state).json_summary = Some(Box::new(sink));
+        self
+    }
+
+    /// Replaces the layout of the progress bar line with a custom template.
+    /// Placeholders between braces are replaced on every frame:
+    ///
+    /// * `{percent}`: percentage completed, padded to three columns.
+    /// * `{bar}`: the bar itself, including its delimiters.
+    /// * `{pos}`: number of items processed, or their total weight for weighted bars.
+    /// * `{len}`: total number of items, or the total weight for weighted bars.
+    /// * `{elapsed}`: time elapsed since the bar started.
+    /// * `{eta}`: estimated time remaining.
+    /// * `{rate}`: items (or weight) processed per second.
+    /// * `{msg}`: the current message, see [`ProgressHandle::set_message`].
+    /// * `{errors}`: number of failed items, see [`ProgressBar::try_progress`].
+    /// * `{queue}`: number of items read ahead, see [`ProgressBar::with_prefetch`].
+    /// * `{prefix}`: label of the bar, see [`ProgressBar::with_prefix`].
+    /// * `{spinner}`: spinner advancing on every tick, see [`ProgressBar::with_heartbeat`].
+    /// * `{unit}`: name of the items counted, see [`ProgressBar::with_unit`].
+    ///
+    /// Fields that are not known for unbounded bars render as `?`. Counts of
+    /// 100,000 and more are compacted with a decimal prefix and one decimal,
+    /// `{pos}` and `{len}` sharing the prefix of the larger: `1.2M/8.0M`.
+    ///
+    /// Fields can be laid out in columns so the line does not shift as values
+    /// change width: `{pos:6}` pads the field to six columns, numbers being
+    /// aligned right and text left unless `{msg:>6}` or `{pos:<6}` says
+    /// otherwise. Text longer than its column is truncated, numbers are kept
+    /// whole. `{msg:*}` makes a field elastic: it is truncated so the line fits
+    /// [`ProgressBar::with_max_width`], elastic fields sharing the columns left.
+    ///
+    /// # Example
+    /// ```
+    /// use cpbar::*;
+    /// let progress_bar = ProgressBar::new((0..6))
+    ///                 .with_bounds()
+    ///                 .with_template("{pos}/{len} {bar} eta: {eta}");
+    ///
+    /// let aligned = ProgressBar::new((0..1500))
+    ///                 .with_bounds()
+    ///                 .with_template("{percent}% {pos:5}/{len} {rate:>12} {msg:*}");
+    ///
+    /// let capture = testing::Capture::new();
+    /// let large = ProgressBar::new((0..8_000_000))
+    ///                 .with_bounds()
+    ///                 .with_template("{pos}/{len}")
+    ///                 .with_draw_target(capture.clone());
+    /// large.handle().inc(1_234_567);
+    /// capture.assert_last_frame("1.2M/8.0M");
+    /// ```
+    ///
+    pub fn with_template(self, template: &str) -> Self {
+        lock(&self.state).template = Some(Template::parse(template));
+        self
+    }
+
+    /// Selects how `{elapsed}` and `{eta}` durations are displayed.
+    ///
+    /// # Example
+    /// ```
+    /// use cpbar::*;
+    /// let progress_bar = ProgressBar::new((0..6))
+    ///                 .with_bounds()
+    ///                 .with_duration_format(DurationFormat::HumanShort);
+    /// ```
+    ///
+    pub fn with_duration_format(self, format: DurationFormat) -> Self {
+        lock(&self.state).durations = format;
+        self
+    }
+
+    /// Estimates the `{rate}` and `{eta}` fields from recent progress with
+    /// `estimator` instead of the average since the start, so they follow
+    /// workloads whose speed changes. See [`RateEstimator`].
+    ///
+    /// # Example
+    /// ```
+    /// use cpbar::*;
+    /// let progress_bar = ProgressBar::new((0..100))
+    ///                 .with_bounds()
+    ///                 .with_rate_estimator(RateEstimator::SlidingWindow { secs: 5.0 })
+    ///                 .with_template("{bar} {rate} eta {eta}");
+    /// ```
+    ///
+    pub fn with_rate_estimator(self, estimator: RateEstimator) -> Self {
+        lock(&self.state).rate = Some(RateTracker::new(estimator));
+        self
+    }
+
+    /// Displays `{pos}`, `{len}` and `{rate}` with scaled units that adapt as
+    /// the values cross unit boundaries, see [`Units`].
+    ///
+    /// # Example
+    /// ```
+    /// use cpbar::*;
+    /// let chunks = vec![4096u64; 1000];
+    /// let progress_bar = ProgressBar::new(chunks.iter())
+    ///                 .with_weights(4096 * 1000, |size| **size)
+    ///                 .with_template("{bar} {pos} of {len} at {rate}")
+    ///                 .with_units(Units::binary("B"));
+    /// ```
+    ///
+    pub fn with_units(self, units: Units) -> Self {
+        lock(&self.state).units = Some(ScaledUnits::new(units));
+        self
+    }
+
+    /// Names the items counted, such as `"records"` or `"requests"`. Positions
+    /// and totals are shown with thousands separators, the rate per unit, and
+    /// unbounded bars default to a counter line: `12,345 records  1,043 rec/s`.
+    /// Scaled [`Units`] take precedence when both are set.
+    ///
+    /// # Example
+    /// ```
+    /// use cpbar::*;
+    /// use std::io::Write;
+    /// use std::sync::{Arc, Mutex};
+    /// use std::time::Duration;
+    ///
+    /// #[derive(Clone, Default)]
+    /// struct Shared(Arc<Mutex<Vec<u8>>>);
+    ///
+    /// impl Write for Shared {
+    ///     fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+    ///         self.0.lock().unwrap().write(buf)
+    ///     }
+    ///     fn flush(&mut self) -> std::io::Result<()> {
+    ///         Ok(())
+    ///     }
+    /// }
+    ///
+    /// let sink = Shared::default();
+    /// let progress_bar = ProgressBar::new((0..12_345))
+    ///                 .with_unit("records")
+    ///                 .with_template("{pos} {unit}")
+    ///                 .with_mirror(sink.clone(), Duration::ZERO);
+    /// for _ in progress_bar {}
+    ///
+    /// let written = String::from_utf8(sink.0.lock().unwrap().clone()).unwrap();
+    /// assert_eq!(written.lines().last(), Some("12,345 records"));
+    /// ```
+    ///
+    pub fn with_unit(self, unit: &str) -> Self {
+        lock(&self.state).unit = Some(unit.to_string());
+        self
+    }
+
+    /// Splits the bar in stacked segments, one per class of items, each
+    /// taking a share of the bar proportional to its count. The loop body
+    /// classifies every item with [`ProgressHandle::inc_segment`], passing the
+    /// index of its segment in `segments`, and each count is available to
+    /// templates as a field named after its segment.
+    ///
+    /// # Example
+    /// ```
+    /// use cpbar::*;
+    /// use std::io::Write;
+    /// use std::sync::{Arc, Mutex};
+    /// use std::time::Duration;
+    ///
+    /// #[derive(Clone, Default)]
+    /// struct Shared(Arc<Mutex<Vec<u8>>>);
+    ///
+    /// impl Write for Shared {
+    ///     fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+    ///         self.0.lock().unwrap().write(buf)
+    ///     }
+    ///     fn flush(&mut self) -> std::io::Result<()> {
+    ///         Ok(())
+    ///     }
+    /// }
+    ///
+    /// const OK: SegmentId = SegmentId(0);
+    /// const SKIPPED: SegmentId = SegmentId(1);
+    /// const FAILED: SegmentId = SegmentId(2);
+    ///
+    /// let sink = Shared::default();
+    /// let rows = vec!["a", "", "b", "?", "c", "d", "", "e"];
+    /// let progress_bar = ProgressBar::new(rows.iter())
+    ///                 .with_bounds()
+    ///                 .with_segments(&[
+    ///                     Segment::ok(),
+    ///                     Segment::new("skipped", '-'),
+    ///                     Segment::new("failed", 'x').with_color(31),
+    ///                 ])
+    ///                 .with_template("{bar} {ok} ok, {skipped} skipped, {failed} failed")
+    ///                 .with_mirror(sink.clone(), Duration::ZERO);
+    /// let handle = progress_bar.handle();
+    /// for row in progress_bar {
+    ///     match *row {
+    ///         "" => handle.inc_segment(SKIPPED),
+    ///         "?" => handle.inc_segment(FAILED),
+    ///         _ => handle.inc_segment(OK),
+    ///     }
+    /// }
+    ///
+    /// let written = String::from_utf8(sink.0.lock().unwrap().clone()).unwrap();
+    /// assert_eq!(
+    ///     written.lines().last(),
+    ///     Some("[█████--x] 5 ok, 2 skipped, 1 failed")
+    /// );
+    /// assert_eq!(handle.segment(SKIPPED), 2);
+    /// ```
+    ///
+    pub fn with_segments(self, segments: &[Segment]) -> Self {
+        lock(&self.state).segments = segments.iter().map(|segment| (segment.clone(), 0)).collect();
+        self
+    }
+
+    /// Mirrors every frame to a secondary sink (a file, a socket, a log pipe...).
+    /// Bursts of updates are coalesced so the sink receives at most one frame per
+    /// `interval`, always the latest one, and the final state is written when the
+    /// bar finishes. Each frame is written as a plain line without escape codes,
+    /// a frame identical to the previous one is not written again.
+    ///
+    /// A coalesced frame is written once `interval` elapsed on the clock of the
+    /// bar, even while the bar is idle: unless the bar has a steady tick, one is
+    /// started at `interval`, see [`ProgressBar::enable_steady_tick`].
+    ///
+    /// # Example
+    /// ```
+    /// use cpbar::*;
+    /// use std::io::Write;
+    /// use std::sync::{Arc, Mutex};
+    /// use std::time::Duration;
+    ///
+    /// #[derive(Clone, Default)]
+    /// struct Shared(Arc<Mutex<Vec<u8>>>);
+    ///
+    /// impl Write for Shared {
+    ///     fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+    ///         self.0.lock().unwrap().write(buf)
+    ///     }
+    ///     fn flush(&mut self) -> std::io::Result<()> {
+    ///         Ok(())
+    ///     }
+    /// }
+    ///
+    /// let sink = Shared::default();
+    /// let progress_bar = ProgressBar::new((0..100))
+    ///                 .with_bounds()
+    ///                 .with_template("{pos}/{len}")
+    ///                 .with_mirror(sink.clone(), Duration::from_secs(3600));
+    /// for _ in progress_bar {}
+    ///
+    /// // Only the first frame and the final state reached the slow sink.
+    /// let written = String::from_utf8(sink.0.lock().unwrap().clone()).unwrap();
+    /// assert_eq!(written, "0/100\n100/100\n");
+    ///
+    /// // An idle bar still writes its latest frame once the interval elapsed.
+    /// let clock = testing::MockClock::new();
+    /// let sink = Shared::default();
+    /// let mut progress_bar = ProgressBar::new((0..3))
+    ///                 .with_bounds()
+    ///                 .with_template("{pos}/{len}")
+    ///                 .with_clock(clock.clone())
+    ///                 .with_draw_target(testing::Capture::new())
+    ///                 .with_mirror(sink.clone(), Duration::from_millis(20));
+    /// progress_bar.next();
+    /// progress_bar.next();
+    /// std::thread::sleep(Duration::from_millis(100));
+    /// assert_eq!(*sink.0.lock().unwrap(), b"0/3\n");
+    /// clock.advance(Duration::from_millis(20));
+    /// std::thread::sleep(Duration::from_millis(100));
+    /// assert_eq!(*sink.0.lock().unwrap(), b"0/3\n1/3\n");
+    /// ```
+    ///
+    pub fn with_mirror<W>(mut self, sink: W, interval: std::time::Duration) -> Self
+    where
+        W: std::io::Write + Send + 'static,
+    {
+        {
+            let mut state = lock(&self.state);
+            let mirror = Mirror::new(Box::new(sink), interval, state.timing.clone());
+            state.target.mirrors.push(mirror);
+        }
+        if self.ticker.is_none() && !interval.is_zero() {
+            self.ticker = Some(Ticker::spawn(&self.state, interval));
+        }
+        self
+    }
+
+    /// Only updates the shared state and draws every `n` items, counting the
+    /// items in between locally, for hot loops where locking the state on
+    /// every item is not affordable. The bar and the timing statistics then
+    /// advance by batches of `n` items.
+    ///
+    /// # Example
+    /// ```
+    /// use cpbar::*;
+    /// let total: u64 = ProgressBar::new((0..1_000_000u32))
+    ///     .with_bounds()
+    ///     .with_batch(10_000)
+    ///     .map(u64::from)
+    ///     .sum();
+    /// assert_eq!(total, 499_999_500_000);
+    /// ```
+    ///
+    pub fn with_batch(mut self, n: usize) -> Self {
+        self.batch.size = n;
+        self
+    }
+
+    /// Advances the spinner and redraws the bar without moving it, see
+    /// [`ProgressBar::with_heartbeat`]. A heartbeat is not an item: the
+    /// statistics, the rate and the milestones are left untouched.
+    ///
+    /// # Example
+    /// ```
+    /// use cpbar::*;
+    /// let mut progress_bar = ProgressBar::new((0..3)).with_bounds();
+    /// let handle = progress_bar.handle();
+    /// for _ in progress_bar.by_ref() {
+    ///     handle.tick();
+    ///     handle.tick();
+    /// }
+    /// assert_eq!(progress_bar.stats().count, 3);
+    /// ```
+    ///
+    pub fn tick(&self) {
+        let mut state = lock(&self.state);
+        if !state.finished {
+            state.heartbeat();
+        }
+    }
+
+    /// Returns a handle that stays usable while the bar is consumed by a loop.
+    ///
+    /// # Example
+    /// ```
+    /// use cpbar::*;
+    /// let progress_bar = ProgressBar::new((0..6)).with_bounds();
+    /// let handle = progress_bar.handle();
+    /// for i in progress_bar {
+    ///     handle.println_done("Finished", &format!("step {}", i));
+    /// }
+    /// ```
+    ///
+    pub fn handle(&self) -> ProgressHandle {
+        ProgressHandle::new(Arc::clone(&self.state))
+    }
+
+    /// Starts a timed phase of the work, which ends when the returned guard is
+    /// dropped. Phases are listed by [`Self::phases`] and in the summary of
+    /// [`Self::with_summary`], see [`Task`].
+    ///
+    /// # Example
+    /// ```
+    /// use cpbar::*;
+    /// let multi = MultiProgress::new();
+    /// let progress_bar = multi.add(ProgressBar::new((0..3)).with_bounds());
+    /// let handle = progress_bar.handle();
+    /// for _ in progress_bar {
+    ///     let parsing = handle.task("parsing");
+    ///     parsing.set_message("reading tokens");
+    ///     drop(parsing);
+    ///     let _linking = handle.task("linking");
+    /// }
+    /// let phases = handle.phases();
+    /// assert_eq!(phases.len(), 2);
+    /// assert_eq!(phases[0].name, "parsing");
+    /// assert_eq!(phases[0].count, 3);
+    /// assert_eq!(phases[1].name, "linking");
+    /// ```
+    ///
+    pub fn task(&self, name: &str) -> Task {
+        Task::start(&self.state, name)
+    }
+
+    /// Time spent in the tasks that ended, one [`Phase`] per name, in the
+    /// order the names first ended.
+    pub fn phases(&self) -> Vec<Phase> {
+        lock(&self.state).phases.clone()
+    }
+
+    /// Stable identifier of the bar, see [`MultiProgress::get`].
+    pub fn id(&self) -> BarId {
+        lock(&self.state).id
+    }
+
+    /// Prints a line above the bar without corrupting it.
+    ///
+    /// # Example
+    /// ```
+    /// use cpbar::*;
+    /// let mut progress_bar = ProgressBar::new((0..3)).with_bounds();
+    /// while let Some(i) = progress_bar.next() {
+    ///     progress_bar.println(&format!("item {}", i));
+    /// }
+    /// ```
+    ///
+    pub fn println(&self, message: &str) {
+        lock(&self.state).target.println(message);
+    }
+
+    /// Erases the bar, runs `f` and redraws the bar. See [`ProgressHandle::suspend`].
+    pub fn suspend<R>(&self, f: impl FnOnce() -> R) -> R {
+        draw::suspend(&self.state, f)
+    }
+
+    /// Prints a finished step above the bar, `status` right-aligned. See
+    /// [`ProgressHandle::println_done`].
+    pub fn println_done(&self, status: &str, message: &str) {
+        lock(&self.state)
+            .target
+            .println(&status_line(status, message));
+    }
+
+    /// Changes the characters used to draw the bar.
+    ///
+    /// # Example
+    /// ```
+    /// use cpbar::*;
+    /// let progress_bar = ProgressBar::new((0..6))
+    ///                 .with_bounds()
+    ///                 .with_style(BarStyle::new("#-"));
+    /// ```
+    ///
+    pub fn with_style(self, style: BarStyle) -> Self {
+        lock(&self.state).style = style;
+        self
+    }
+
+    /// Applies one of the ready-made looks: its bar style, its delimiters and,
+    /// for [`Preset::Minimal`], its template. Later `with_*` calls override it.
+    ///
+    /// # Example
+    /// ```
+    /// use cpbar::*;
+    /// let progress_bar = ProgressBar::new((0..6))
+    ///                 .with_bounds()
+    ///                 .with_preset(Preset::Ascii);
+    /// ```
+    ///
+    pub fn with_preset(self, preset: Preset) -> Self {
+        {
+            let mut state = lock(&self.state);
+            state.style = preset.style();
+            state.delims = preset.delims();
+            if let Some(template) = preset.template() {
+                state.template = Some(Template::parse(template));
+            }
+        }
+        self
+    }
+
+    /// Sets a label displayed at the start of the line, before the default
+    /// templates, and by the `{prefix}` field of custom ones. Within a
+    /// [`MultiProgress`] prefixes are padded to the longest one, so bars with
+    /// different labels line up.
+    ///
+    /// # Example
+    /// ```
+    /// use cpbar::*;
+    ///
+    /// let multi = MultiProgress::new();
+    /// let mut fetch = multi.add(ProgressBar::new(0..4).with_bounds().with_prefix("fetch"));
+    /// let mut extract = multi.add(
+    ///     ProgressBar::new(0..4)
+    ///         .with_bounds()
+    ///         .with_prefix("extract")
+    ///         .with_template("{prefix}|{pos}/{len}"),
+    /// );
+    /// fetch.next();
+    /// extract.next();
+    /// assert!(fetch.to_string().starts_with("fetch     0% "));
+    /// assert_eq!(extract.to_string(), "extract|0/4");
+    /// ```
+    ///
+    pub fn with_prefix(self, prefix: &str) -> Self {
+        lock(&self.state).set_prefix(prefix);
+        self
+    }
+
+    /// Reserves exactly `width` columns for the prefix: shorter prefixes are
+    /// padded with spaces and longer ones are truncated with an ellipsis.
+    ///
+    /// # Example
+    /// ```
+    /// use cpbar::*;
+    /// let mut progress_bar = ProgressBar::new((0..4))
+    ///     .with_bounds()
+    ///     .with_prefix("downloading")
+    ///     .with_prefix_width(6)
+    ///     .with_template("{prefix}|{pos}/{len}");
+    /// progress_bar.next();
+    /// assert_eq!(progress_bar.to_string(), "downl…|0/4");
+    /// ```
+    ///
+    pub fn with_prefix_width(self, width: usize) -> Self {
+        lock(&self.state).prefix_width = Some(width);
+        self
+    }
+
+    /// Sets the message displayed by the `{msg}` template field.
+    ///
+    /// # Example
+    /// ```
+    /// use cpbar::*;
+    /// let progress_bar = ProgressBar::new((0..6))
+    ///                 .with_bounds()
+    ///                 .with_template("{bar} {msg}")
+    ///                 .with_message("warming up");
+    /// ```
+    ///
+    pub fn with_message(self, message: &str) -> Self {
+        lock(&self.state).message = message.to_string();
+        self
+    }
+
+    /// Eases the bar cells toward the real position instead of jumping when
+    /// an item completes: every frame covers `factor` of the remaining gap,
+    /// so values between 0.2 and 0.5 give a smooth animation. Needs
+    /// [`Self::enable_steady_tick`] to draw the frames in between items. Purely
+    /// visual, the counters and the percentage always show the real position.
+    ///
+    /// # Example
+    /// ```
+    /// use cpbar::*;
+    /// use std::time::Duration;
+    /// let progress_bar = ProgressBar::new((0..4))
+    ///     .with_bounds()
+    ///     .with_smoothing(0.3)
+    ///     .enable_steady_tick(Duration::from_millis(30));
+    /// for _ in progress_bar {
+    ///     std::thread::sleep(Duration::from_millis(100));
+    /// }
+    /// ```
+    ///
+    pub fn with_smoothing(self, factor: f64) -> Self {
+        let mut state = lock(&self.state);
+        state.smoothing = Some(factor);
+        state.shown = state.position as f64;
+        drop(state);
+        self
+    }
+
+    /// Reserves exactly `width` columns for the `{msg}` field: shorter messages
+    /// are padded with spaces and longer ones are truncated with an ellipsis,
+    /// so the fields after the message stay in place as it changes. Wide
+    /// characters such as CJK ideographs count as two columns.
+    ///
+    /// # Example
+    /// ```
+    /// use cpbar::*;
+    /// # use std::io::Write;
+    /// # use std::sync::{Arc, Mutex};
+    /// # #[derive(Clone, Default)]
+    /// # struct Shared(Arc<Mutex<Vec<u8>>>);
+    /// # impl Write for Shared {
+    /// #     fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+    /// #         self.0.lock().unwrap().write(buf)
+    /// #     }
+    /// #     fn flush(&mut self) -> std::io::Result<()> {
+    /// #         Ok(())
+    /// #     }
+    /// # }
+    /// let sink = Shared::default();
+    /// let progress_bar = ProgressBar::new(["a.txt", "archive.tar.gz", "資料.zip"].iter())
+    ///                 .with_bounds()
+    ///                 .with_template("{msg}|{pos}/{len}")
+    ///                 .with_message_width(8)
+    ///                 .with_mirror(sink.clone(), std::time::Duration::ZERO);
+    /// let handle = progress_bar.handle();
+    /// for name in progress_bar {
+    ///     handle.set_message(name);
+    /// }
+    /// let written = String::from_utf8(sink.0.lock().unwrap().clone()).unwrap();
+    /// assert_eq!(
+    ///     written.lines().collect::<Vec<_>>(),
+    ///     [
+    ///         "        |0/3",
+    ///         "a.txt   |0/3",
+    ///         "a.txt   |1/3",
+    ///         "archive…|1/3",
+    ///         "archive…|2/3",
+    ///         "資料.zip|2/3",
+    ///         "資料.zip|3/3",
+    ///     ]
+    /// );
+    /// ```
+    ///
+    pub fn with_message_width(self, width: usize) -> Self {
+        lock(&self.state).message_width = Some(width);
+        self
+    }
+
+    /// Columns the line must fit in, shared by the elastic fields of the
+    /// template. Defaults to the width of the terminal, queried again every
+    /// second so the line follows resizes, then to the `COLUMNS` environment
+    /// variable and to 80 columns when the output is not a terminal. Lines
+    /// still too long are cut with an ellipsis. Custom draw targets only get
+    /// their lines cut when a width is set.
+    ///
+    /// # Example
+    /// ```
+    /// use cpbar::*;
+    /// use std::io::Write;
+    /// use std::sync::{Arc, Mutex};
+    ///
+    /// #[derive(Clone, Default)]
+    /// struct Shared(Arc<Mutex<Vec<u8>>>);
+    ///
+    /// impl Write for Shared {
+    ///     fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+    ///         self.0.lock().unwrap().write(buf)
+    ///     }
+    ///     fn flush(&mut self) -> std::io::Result<()> {
+    ///         Ok(())
+    ///     }
+    /// }
+    ///
+    /// let sink = Shared::default();
+    /// let progress_bar = ProgressBar::new(["a.txt", "a-much-longer-name.tar.gz"].iter())
+    ///                 .with_bounds()
+    ///                 .with_template("{pos:3}/{len} {msg:*}|")
+    ///                 .with_max_width(20)
+    ///                 .with_mirror(sink.clone(), std::time::Duration::ZERO);
+    /// let handle = progress_bar.handle();
+    /// for name in progress_bar {
+    ///     handle.set_message(name);
+    /// }
+    /// let written = String::from_utf8(sink.0.lock().unwrap().clone()).unwrap();
+    /// assert_eq!(
+    ///     written.lines().collect::<Vec<_>>(),
+    ///     [
+    ///         "  0/2 |",
+    ///         "  0/2 a.txt|",
+    ///         "  1/2 a.txt|",
+    ///         "  1/2 a-much-longe…|",
+    ///         "  2/2 a-much-longe…|",
+    ///     ]
+    /// );
+    /// ```
+    ///
+    /// Lines without elastic fields are cut too:
+    /// ```
+    /// use cpbar::testing::Capture;
+    /// use cpbar::*;
+    ///
+    /// let capture = Capture::new();
+    /// let progress_bar = ProgressBar::new(0..3)
+    ///     .with_message("copying the files")
+    ///     .with_template("{pos}/3 {msg}")
+    ///     .with_max_width(12)
+    ///     .with_draw_target(capture.clone());
+    /// for _ in progress_bar {}
+    /// capture.assert_last_frame("3/3 copying…");
+    /// ```
+    ///
+    pub fn with_max_width(self, width: usize) -> Self {
+        lock(&self.state).max_width = Some(width);
+        self
+    }
+
+    /// Applies `process` to every frame before it is written, for instance to
+    /// tag the line with the name of a worker, add a timestamp or strip colors.
+    /// A lighter alternative to rendering the whole line yourself.
+    ///
+    /// # Example
+    /// ```
+    /// use cpbar::*;
+    /// # use std::io::Write;
+    /// # use std::sync::{Arc, Mutex};
+    /// # #[derive(Clone, Default)]
+    /// # struct Shared(Arc<Mutex<Vec<u8>>>);
+    /// # impl Write for Shared {
+    /// #     fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+    /// #         self.0.lock().unwrap().write(buf)
+    /// #     }
+    /// #     fn flush(&mut self) -> std::io::Result<()> {
+    /// #         Ok(())
+    /// #     }
+    /// # }
+    /// let sink = Shared::default();
+    /// let progress_bar = ProgressBar::new((0..2))
+    ///                 .with_bounds()
+    ///                 .with_template("{pos}/{len}")
+    ///                 .with_frame_processor(|frame| format!("[worker-3] {}", frame))
+    ///                 .with_mirror(sink.clone(), std::time::Duration::ZERO);
+    /// for _ in progress_bar {}
+    /// let written = String::from_utf8(sink.0.lock().unwrap().clone()).unwrap();
+    /// assert_eq!(written.lines().last(), Some("[worker-3] 2/2"));
+    /// ```
+    ///
+    pub fn with_frame_processor<F>(self, process: F) -> Self
+    where
+        F: Fn(String) -> String + Send + 'static,
+    {
+        lock(&self.state).frame_processor = Some(Box::new(process));
+        self
+    }
+
+    /// Renders the custom template field `{name}` with `component`, see
+    /// [`ext::Component`]. Built-in fields cannot be overridden.
+    ///
+    /// # Example
+    /// ```
+    /// use cpbar::ext::ProgressState;
+    /// use cpbar::*;
+    ///
+    /// let mut progress_bar = ProgressBar::new((0..4))
+    ///     .with_bounds()
+    ///     .with_template("{pos}/{len} {left} left")
+    ///     .with_component("left", |state: &ProgressState| {
+    ///         (state.length.unwrap_or(0) - state.position).to_string()
+    ///     });
+    /// progress_bar.next();
+    /// progress_bar.next();
+    /// assert_eq!(progress_bar.to_string(), "1/4 3 left");
+    /// ```
+    ///
+    pub fn with_component<C>(self, name: &str, component: C) -> Self
+    where
+        C: ext::Component + 'static,
+    {
+        lock(&self.state)
+            .components
+            .push((name.to_string(), Box::new(component)));
+        self
+    }
+
+    /// Draws the bar with `renderer` instead of the template, see
+    /// [`ext::Renderer`]. Templates, styles and frame processors no longer
+    /// apply.
+    ///
+    /// # Example
+    /// ```
+    /// use cpbar::ext::{ProgressState, Renderer};
+    /// use cpbar::*;
+    /// use std::io::{self, Write};
+    /// use std::sync::{Arc, Mutex};
+    ///
+    /// struct Recorder(Arc<Mutex<Vec<u64>>>);
+    /// impl Renderer for Recorder {
+    ///     fn render(&mut self, state: &ProgressState, _out: &mut dyn Write) -> io::Result<()> {
+    ///         self.0.lock().unwrap().push(state.position);
+    ///         Ok(())
+    ///     }
+    /// }
+    ///
+    /// let drawn = Arc::new(Mutex::new(Vec::new()));
+    /// for _ in ProgressBar::new((0..3)).with_renderer(Recorder(drawn.clone())) {}
+    /// assert_eq!(*drawn.lock().unwrap(), [0, 1, 2, 3]);
+    /// ```
+    ///
+    /// The frames written by the renderer go to the target of the bar:
+    /// ```
+    /// use cpbar::ext::{ProgressState, Renderer};
+    /// use cpbar::testing::Capture;
+    /// use cpbar::*;
+    /// use std::io::{self, Write};
+    ///
+    /// struct Dots;
+    /// impl Renderer for Dots {
+    ///     fn render(&mut self, state: &ProgressState, out: &mut dyn Write) -> io::Result<()> {
+    ///         write!(out, "[{}]", ".".repeat(state.position as usize))
+    ///     }
+    ///     fn finish(&mut self, _state: &ProgressState, out: &mut dyn Write) -> io::Result<()> {
+    ///         write!(out, "done")
+    ///     }
+    /// }
+    ///
+    /// let capture = Capture::new();
+    /// let progress_bar = ProgressBar::new((0..2))
+    ///     .with_renderer(Dots)
+    ///     .with_draw_target(capture.clone());
+    /// for _ in progress_bar {}
+    /// capture.assert_frames(&["[]", "[.]", "[..]", "done"]);
+    /// ```
+    ///
+    pub fn with_renderer<R>(self, renderer: R) -> Self
+    where
+        R: ext::Renderer + 'static,
+    {
+        lock(&self.state).renderer = Some(Box::new(renderer));
+        self
+    }
+
+    /// Sends the frames and lines of the bar to `target` instead of the
+    /// console, see [`ext::DrawTarget`].
+    ///
+    /// # Example
+    /// ```
+    /// use cpbar::*;
+    /// use std::sync::mpsc;
+    ///
+    /// struct Channel(mpsc::Sender<String>);
+    /// impl ext::DrawTarget for Channel {
+    ///     fn draw(&mut self, frame: &str) {
+    ///         let _ = self.0.send(frame.to_string());
+    ///     }
+    /// }
+    ///
+    /// let (sender, frames) = mpsc::channel();
+    /// let progress_bar = ProgressBar::new((0..3))
+    ///     .with_bounds()
+    ///     .with_template("{pos}/{len}")
+    ///     .with_draw_target(Channel(sender));
+    /// for _ in progress_bar {}
+    /// assert_eq!(frames.try_iter().last(), Some("3/3".to_string()));
+    /// ```
+    ///
+    pub fn with_draw_target<T>(self, target: T) -> Self
+    where
+        T: ext::DrawTarget + 'static,
+    {
+        lock(&self.state).target.backend = Some(Box::new(target));
+        self
+    }
+
+    /// Measures time with `clock` instead of the system clock, see
+    /// [`ext::Clock`]. The bar starts at the current time of `clock`, and so
+    /// does the deadline of bars built with [`ProgressBar::for_duration`].
+    ///
+    /// # Example
+    /// ```
+    /// use cpbar::testing::MockClock;
+    /// use cpbar::*;
+    /// use std::time::Duration;
+    ///
+    /// let clock = MockClock::new();
+    /// let mut progress_bar = ProgressBar::new(0..10).with_clock(clock.clone());
+    /// progress_bar.next();
+    /// clock.advance(Duration::from_secs(90));
+    /// assert_eq!(progress_bar.elapsed(), Duration::from_secs(90));
+    /// ```
+    ///
+    pub fn with_clock<C>(self, clock: C) -> Self
+    where
+        C: ext::Clock + 'static,
+    {
+        lock(&self.state).timing.set_clock(Arc::new(clock));
+        self
+    }
+
+    /// Computes the `{eta}` field with `estimator`, see [`ext::EtaEstimator`].
+    pub fn with_eta_estimator<E>(self, estimator: E) -> Self
+    where
+        E: ext::EtaEstimator + 'static,
+    {
+        lock(&self.state).eta_estimator = Some(Box::new(estimator));
+        self
+    }
+
+    /// Notifies `observer` as the bar advances, see [`ext::Observer`].
+    ///
+    /// # Example
+    /// ```
+    /// use cpbar::ext::{Observer, ProgressState};
+    /// use cpbar::*;
+    /// use std::sync::{Arc, Mutex};
+    ///
+    /// struct Finished(Arc<Mutex<Option<u64>>>);
+    /// impl Observer for Finished {
+    ///     fn on_finish(&mut self, state: &ProgressState) {
+    ///         *self.0.lock().unwrap() = Some(state.position);
+    ///     }
+    /// }
+    ///
+    /// let total = Arc::new(Mutex::new(None));
+    /// for _ in ProgressBar::new((0..5)).with_observer(Finished(total.clone())) {}
+    /// assert_eq!(*total.lock().unwrap(), Some(5));
+    /// ```
+    ///
+    pub fn with_observer<O>(self, observer: O) -> Self
+    where
+        O: ext::Observer + 'static,
+    {
+        lock(&self.state).observers.push(Box::new(observer));
+        self
+    }
+
+    /// Chooses how the bar replaces its previous frame, see [`RedrawMode`].
+    /// Bars of a [`MultiProgress`] always move the cursor up.
+    ///
+    /// # Example
+    /// ```
+    /// use cpbar::*;
+    /// let progress_bar = ProgressBar::new((0..6))
+    ///                 .with_bounds()
+    ///                 .with_redraw_mode(RedrawMode::SameLine);
+    /// for _ in progress_bar {}
+    /// ```
+    ///
+    pub fn with_redraw_mode(self, mode: RedrawMode) -> Self {
+        lock(&self.state).target.mode = mode;
+        self
+    }
+
+    /// Runs `callback` once for each of the given percentages as the bar
+    /// reaches it, to send a notification or write a log marker. Milestones
+    /// are checked on every tick whether or not a frame is drawn, so none is
+    /// missed with batching or a hidden bar; progress jumping over several
+    /// milestones runs the callback for each of them, in increasing order.
+    /// The bar is locked while the callback runs: it must not use this bar's
+    /// handles.
+    ///
+    /// # Example
+    /// ```
+    /// use cpbar::*;
+    /// use std::sync::{Arc, Mutex};
+    ///
+    /// let reached = Arc::new(Mutex::new(Vec::new()));
+    /// let log = Arc::clone(&reached);
+    /// let progress_bar = ProgressBar::new((0..10))
+    ///                 .with_bounds()
+    ///                 .with_batch(4)
+    ///                 .with_milestones(vec![25, 50, 75, 100], move |percent| {
+    ///                     log.lock().unwrap().push(percent);
+    ///                 });
+    /// for _ in progress_bar {}
+    /// assert_eq!(*reached.lock().unwrap(), vec![25, 50, 75, 100]);
+    /// ```
+    ///
+    pub fn with_milestones<P, F>(self, percents: P, callback: F) -> Self
+    where
+        P: IntoIterator<Item = u8>,
+        F: FnMut(u8) + Send + 'static,
+    {
+        let notifier = Notifier::new(percents.into_iter().collect(), Box::new(callback));
+        lock(&self.state).notifiers.push(notifier);
+        self
+    }
+
+    /// Replaces the redrawn line with plain sentences printed at every 25%,
+    /// for screen readers and logs: `25% complete, about 2 minutes remaining.`
+    /// They contain no control characters. Unbounded bars print a single
+    /// sentence when they finish. Bars start in plain mode when the
+    /// `CPBAR_PLAIN` environment variable is set to anything but an empty
+    /// string or `0`.
+    ///
+    /// # Example
+    /// ```
+    /// use cpbar::*;
+    /// let progress_bar = ProgressBar::new((0..8))
+    ///                 .with_bounds()
+    ///                 .with_prefix("backup")
+    ///                 .with_plain_mode();
+    /// for _ in progress_bar {
+    ///     // backup: 25% complete, less than a second remaining.
+    /// }
+    ///
+    /// // Progress jumping over several milestones reports each of them.
+    /// let capture = testing::Capture::new();
+    /// let progress_bar = ProgressBar::new((0..8))
+    ///                 .with_bounds()
+    ///                 .with_plain_mode()
+    ///                 .with_clock(testing::MockClock::new())
+    ///                 .with_draw_target(capture.clone());
+    /// progress_bar.handle().inc(6);
+    /// assert_eq!(capture.printed(), [
+    ///     "25% complete, less than a second remaining.",
+    ///     "50% complete, less than a second remaining.",
+    ///     "75% complete, less than a second remaining.",
+    /// ]);
+    /// ```
+    ///
+    pub fn with_plain_mode(self) -> Self {
+        lock(&self.state).plain = Some(Milestones::new(PLAIN_STEP));
+        self
+    }
 
-/// Unbounded iterator type state. This is used by the internal API
-/// in order to accept unbounded iterators.
-#[doc(hidden)]
-pub struct Unbounded;
+    /// Reports the progress to the operating system as well: the taskbar
+    /// indicator of terminals supporting `OSC 9;4`, the window title, or
+    /// both, see [`OsProgress`]. The title shows the percentage followed by
+    /// the prefix, or the message when there is none. Both are cleared when
+    /// the bar finishes. Nothing is sent when the bar is hidden, in plain
+    /// mode or drawn to a custom [`DrawTarget`](crate::ext::DrawTarget).
+    ///
+    /// # Example
+    /// ```
+    /// use cpbar::*;
+    /// let progress_bar = ProgressBar::new((0..6))
+    ///                 .with_bounds()
+    ///                 .with_prefix("backup")
+    ///                 .with_os_progress(OsProgress::Both);
+    /// for _ in progress_bar {
+    ///     // do operation
+    /// }
+    /// ```
+    ///
+    pub fn with_os_progress(self, mode: OsProgress) -> Self {
+        lock(&self.state).os = Some(OsIndicator::new(mode));
+        self
+    }
 
-// Bounded iterator type state. This is used by the internal API
-/// in order to accept bounded iterators.
-#[doc(hidden)]
-pub struct Bounded {
-    bound: usize,
-    delims: (char, char),
-}
-
-impl ProgressBarDisplay for Bounded {
-    fn display<Iter>(&self, progress: &ProgressBar<Iter, Self>) {
-        let percent = (progress.index * 100) / self.bound;
-        let elapsed_time = std::time::Instant::now() - progress.start;
-        if self.bound < MAX_COLUMN_WIDTH {
-            println!(
-                "{}{:3}% {}{}{}{} {}/{} {:.4} Secs",
-                CLEAR,
-                percent,
-                self.delims.0,
-                "▓".repeat(progress.index),
-                "░".repeat(self.bound - progress.index),
-                self.delims.1,
-                progress.index,
-                self.bound,
-                elapsed_time.as_secs_f64()
-            );
-        } else {
-            let ticks = MAX_COLUMN_WIDTH * percent / 100;
-            println!(
-                "{}{:3}% {}{}{}{} {}/{} {:.4} Secs",
-                CLEAR,
-                percent,
-                self.delims.0,
-                "▓".repeat(ticks),
-                "░".repeat(MAX_COLUMN_WIDTH - ticks),
-                self.delims.1,
-                progress.index,
-                self.bound,
-                elapsed_time.as_secs_f64()
-            );
-        }
-    }
-}
-
-impl ProgressBarDisplay for Unbounded {
-    fn display<Iter>(&self, progress: &ProgressBar<Iter, Self>) {
-        let elapsed_time = std::time::Instant::now() - progress.start;
-        println!(
-            "{}[{} in {:.4} Secs] ",
-            CLEAR,
-            progress.index,
-            elapsed_time.as_secs_f32()
-        );
+    /// Erases the bar line when the bar finishes, so only the lines printed
+    /// through `println_done` remain. This is how build tools keep a transient
+    /// status bar below a growing log of finished steps.
+    ///
+    /// # Example
+    /// ```
+    /// use cpbar::*;
+    /// let progress_bar = ProgressBar::new((0..6)).with_bounds().with_clear_on_finish();
+    /// let handle = progress_bar.handle();
+    /// for i in progress_bar {
+    ///     handle.println_done("Finished", &format!("step {}", i));
+    /// }
+    /// ```
+    ///
+    pub fn with_clear_on_finish(self) -> Self {
+        lock(&self.state).target.clear_on_finish = true;
+        self
+    }
+
+    /// Redraws the bar every `interval` from a background thread, so the elapsed
+    /// time and the ETA keep updating while a slow item is being processed.
+    /// Calling it again changes the interval of the existing thread. The thread
+    /// stops once the bar finishes or is dropped.
+    ///
+    /// # Example
+    /// ```
+    /// use cpbar::*;
+    /// use std::time::Duration;
+    ///
+    /// let progress_bar = ProgressBar::new((0..3))
+    ///                 .with_bounds()
+    ///                 .enable_steady_tick(Duration::from_millis(10));
+    /// for _ in progress_bar {
+    ///     std::thread::sleep(Duration::from_millis(30));
+    /// }
+    /// ```
+    ///
+    pub fn enable_steady_tick(mut self, interval: std::time::Duration) -> Self {
+        match &self.ticker {
+            Some(ticker) => ticker.set_interval(interval),
+            None => self.ticker = Some(Ticker::spawn(&self.state, interval)),
+        }
+        self
     }
 }
 
@@ -124,7 +1613,8 @@ impl<Iter> ProgressBar<Iter, Unbounded>
 where
     Iter: Iterator,
 {
-    /// Create a new progress bar.  Requires an iterator to be passed as argument.
+    /// Create a new progress bar.  Requires an iterator, or anything that can
+    /// be iterated such as a collection, to be passed as argument.
     ///
     /// # Example
     /// ```
@@ -133,30 +1623,455 @@ where
     /// for _ in progress_bar {
     ///     // do operation
     /// }
+    ///
+    /// let names = vec!["serde", "rand", "log"];
+    /// for name in ProgressBar::new(&names).with_bounds() {
+    ///     // do operation
+    /// }
     /// ```
-    pub fn new(iter: Iter) -> Self {
-        println!();
+    pub fn new<I>(iter: I) -> Self
+    where
+        I: IntoIterator<IntoIter = Iter>,
+    {
+        Self::wrap(iter.into_iter())
+    }
+}
+
+/// Bounded bar over the items of a vector.
+///
+/// # Example
+/// ```
+/// use cpbar::*;
+/// let progress_bar = ProgressBar::from(vec![1, 2, 3]);
+/// assert_eq!(progress_bar.length(), Some(3));
+/// assert_eq!(progress_bar.sum::<i32>(), 6);
+/// ```
+impl<T> From<Vec<T>> for ProgressBar<std::vec::IntoIter<T>, Bounded> {
+    fn from(items: Vec<T>) -> Self {
+        ProgressBar::new(items).with_bounds()
+    }
+}
+
+/// Bounded bar over the items of a slice.
+///
+/// # Example
+/// ```
+/// use cpbar::*;
+/// let items = [1, 2, 3];
+/// let progress_bar = ProgressBar::from(&items[..]);
+/// assert_eq!(progress_bar.length(), Some(3));
+/// ```
+impl<'a, T> From<&'a [T]> for ProgressBar<std::slice::Iter<'a, T>, Bounded> {
+    fn from(items: &'a [T]) -> Self {
+        ProgressBar::new(items).with_bounds()
+    }
+}
+
+/// Bounded bar over a range of indices.
+///
+/// # Example
+/// ```
+/// use cpbar::*;
+/// let progress_bar = ProgressBar::from(0..10);
+/// assert_eq!(progress_bar.length(), Some(10));
+/// ```
+impl From<std::ops::Range<usize>> for ProgressBar<std::ops::Range<usize>, Bounded> {
+    fn from(range: std::ops::Range<usize>) -> Self {
+        ProgressBar::new(range).with_bounds()
+    }
+}
+
+impl<Iter> ProgressBar<Iter, Unbounded> {
+    /// Unbounded bar over any kind of sequence.
+    fn wrap(iter: Iter) -> Self {
+        let state = BarState::new().shared();
         Self {
+            guard: DropGuard(Arc::downgrade(&state)),
             iter,
-            index: 0,
-            start: std::time::Instant::now(),
             bound: Unbounded,
+            state,
+            ticker: None,
+            polling: false,
+            batch: Batch::default(),
         }
     }
 }
 
+impl<Iter, Bound> fmt::Debug for ProgressBar<Iter, Bound> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = lock(&self.state);
+        f.debug_struct("ProgressBar")
+            .field("id", &state.id)
+            .field("position", &state.current_position())
+            .field("length", &state.length)
+            .field("elapsed", &state.elapsed())
+            .field("errors", &state.errors)
+            .field("finished", &state.finished)
+            .finish()
+    }
+}
+
+/// Formats the current frame of the bar, without drawing it.
+///
+/// # Example
+/// ```
+/// use cpbar::*;
+/// let mut progress_bar = ProgressBar::new((0..4))
+///     .with_bounds()
+///     .with_template("{pos}/{len} {percent}%");
+/// progress_bar.next();
+/// progress_bar.next();
+/// assert_eq!(progress_bar.to_string(), "1/4  25%");
+/// ```
+///
+impl<Iter, Bound> fmt::Display for ProgressBar<Iter, Bound> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&lock(&self.state).render())
+    }
+}
+
 impl<Iter, Bound> Iterator for ProgressBar<Iter, Bound>
 where
     Iter: Iterator,
-    Bound: ProgressBarDisplay,
+    Bound: ProgressBarWeight<Iter::Item>,
 {
     type Item = Iter::Item;
     fn next(&mut self) -> Option<Self::Item> {
-        self.bound.display(&self);
-        self.index += 1;
-        self.iter.next()
+        self.step(Iterator::next)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+impl<Iter, Bound> ProgressBar<Iter, Bound>
+where
+    Iter: Iterator,
+    Bound: ProgressBarWeight<Iter::Item>,
+{
+    /// Hands out the next item pulled from either end of the inner iterator.
+    fn step<F>(&mut self, pull: F) -> Option<Iter::Item>
+    where
+        F: Fn(&mut Iter) -> Option<Iter::Item>,
+    {
+        if self.batch.count > 0 && self.batch.count < self.batch.size {
+            if let Some(item) = pull(&mut self.iter) {
+                self.batch.count += 1;
+                self.batch.weight += self.bound.weight(&item);
+                self.batch.errors += self.bound.failed(&item) as u64;
+                return Some(item);
+            }
+            self.request();
+            self.receive::<Iter::Item>(None);
+            return None;
+        }
+        self.request();
+        self.batch.count = 1;
+        let item = pull(&mut self.iter);
+        self.receive(item.as_ref());
+        item
+    }
+}
+
+/// Items taken from the back count as progress like the ones from the front,
+/// so a bounded bar still reaches its total when consumed in reverse or from
+/// both ends.
+///
+/// # Example
+/// ```
+/// use cpbar::*;
+/// let mut progress_bar = ProgressBar::new(vec![1, 2, 3, 4]).with_bounds();
+/// assert_eq!(progress_bar.next_back(), Some(4));
+/// assert_eq!(progress_bar.next(), Some(1));
+/// assert_eq!(progress_bar.len(), 2);
+/// assert_eq!(progress_bar.by_ref().rev().collect::<Vec<_>>(), vec![3, 2]);
+/// assert_eq!(progress_bar.position(), 4);
+/// assert_eq!(progress_bar.length(), Some(4));
+/// ```
+///
+impl<Iter, Bound> DoubleEndedIterator for ProgressBar<Iter, Bound>
+where
+    Iter: DoubleEndedIterator,
+    Bound: ProgressBarWeight<Iter::Item>,
+{
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.step(DoubleEndedIterator::next_back)
+    }
+}
+
+impl<Iter, Bound> ExactSizeIterator for ProgressBar<Iter, Bound>
+where
+    Iter: ExactSizeIterator,
+    Bound: ProgressBarWeight<Iter::Item>,
+{
+    fn len(&self) -> usize {
+        self.iter.len()
+    }
+}
+
+impl<Iter, Bound> FusedIterator for ProgressBar<Iter, Bound>
+where
+    Iter: FusedIterator,
+    Bound: ProgressBarWeight<Iter::Item>,
+{
+}
+impl<Iter> ProgressBar<Iter, Unbounded>
+where
+    Iter: Iterator,
+{
+    /// Initializes a progress bar where each item contributes a different amount
+    /// of work, such as files of different sizes. Every item consumed advances
+    /// the bar by `weigh(item)` out of `total`, which drives the percentage,
+    /// the rate and the ETA.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use cpbar::*;
+    /// let files = vec![("small.txt", 10u64), ("big.iso", 4_000)];
+    /// let total = files.iter().map(|(_, size)| size).sum();
+    /// for (name, size) in ProgressBar::new(files.iter()).with_weights(total, |(_, size)| *size) {
+    ///     // process the file
+    /// }
+    /// ```
+    ///
+    pub fn with_weights<F>(self, total: u64, weigh: F) -> ProgressBar<Iter, Weighted<F>>
+    where
+        F: Fn(&Iter::Item) -> u64,
+    {
+        lock(&self.state).set_length(total, usize::MAX);
+        self.with_state(Weighted { weigh })
+    }
+}
+
+impl<Iter> ProgressBar<Iter, Unbounded>
+where
+    Iter: Iterator,
+{
+    /// Displays an approximate total for sequences whose length is unknown but
+    /// can be estimated, such as variable-length records read from a file of
+    /// known size. Every `every` items, `estimate` receives the current position
+    /// and may return a revised total, rendered with a `≈` marker. The exact
+    /// count is displayed once the sequence ends.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use cpbar::*;
+    /// use std::sync::atomic::{AtomicU64, Ordering};
+    /// use std::sync::Arc;
+    /// # use std::io::Write;
+    /// # use std::sync::Mutex;
+    /// # #[derive(Clone, Default)]
+    /// # struct Shared(Arc<Mutex<Vec<u8>>>);
+    /// # impl Write for Shared {
+    /// #     fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+    /// #         self.0.lock().unwrap().write(buf)
+    /// #     }
+    /// #     fn flush(&mut self) -> std::io::Result<()> {
+    /// #         Ok(())
+    /// #     }
+    /// # }
+    /// # let sink = Shared::default();
+    ///
+    /// let file_size = 10_000u64;
+    /// let bytes_read = Arc::new(AtomicU64::new(0));
+    /// let records = (0..100).map(|i| 80 + i % 40);
+    ///
+    /// let seen = Arc::clone(&bytes_read);
+    /// let progress_bar = ProgressBar::new(records).with_estimator(10, move |records| {
+    ///     let bytes = seen.load(Ordering::Relaxed);
+    ///     (bytes > 0).then(|| records * file_size / bytes)
+    /// });
+    /// # let progress_bar = progress_bar
+    /// #     .with_template("{pos}/{len}")
+    /// #     .with_mirror(sink.clone(), std::time::Duration::ZERO);
+    /// for record_len in progress_bar {
+    ///     bytes_read.fetch_add(record_len, Ordering::Relaxed);
+    /// }
+    /// # let written = String::from_utf8(sink.0.lock().unwrap().clone()).unwrap();
+    /// # let frames: Vec<&str> = written.lines().collect();
+    /// # assert_eq!(frames[0], "0/?");
+    /// # assert_eq!(frames[11], "11/≈118");
+    /// # assert_eq!(frames.last(), Some(&"100/100"));
+    /// ```
+    ///
+    pub fn with_estimator<F>(self, every: usize, estimate: F) -> ProgressBar<Iter, Estimated>
+    where
+        F: FnMut(u64) -> Option<u64> + Send + 'static,
+    {
+        lock(&self.state).set_estimator(Estimator {
+            every: every.max(1),
+            revise: Box::new(estimate),
+        });
+        self.with_state(Estimated)
+    }
+
+    /// Uses the size hint of the iterator as a provisional total, displayed
+    /// with a `~` marker: the upper bound when there is one, the lower bound
+    /// otherwise. The total grows if the iterator outlives the estimate, and
+    /// becomes exact once it is exhausted. Without any hint the total stays
+    /// unknown until then, and the bar never reads 100% before the end.
+    ///
+    /// # Example
+    /// ```
+    /// use cpbar::*;
+    /// # use std::io::Write;
+    /// # use std::sync::{Arc, Mutex};
+    /// # #[derive(Clone, Default)]
+    /// # struct Shared(Arc<Mutex<Vec<u8>>>);
+    /// # impl Write for Shared {
+    /// #     fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+    /// #         self.0.lock().unwrap().write(buf)
+    /// #     }
+    /// #     fn flush(&mut self) -> std::io::Result<()> {
+    /// #         Ok(())
+    /// #     }
+    /// # }
+    /// let sink = Shared::default();
+    /// let even = (0..10).filter(|i| i % 2 == 0);
+    /// let progress_bar = ProgressBar::new(even)
+    ///                 .with_estimated_bounds()
+    ///                 .with_template("{pos}/{len}")
+    ///                 .with_mirror(sink.clone(), std::time::Duration::ZERO);
+    /// for _ in progress_bar {}
+    /// let written = String::from_utf8(sink.0.lock().unwrap().clone()).unwrap();
+    /// let frames: Vec<&str> = written.lines().collect();
+    /// assert_eq!(frames[..2], ["0/~10", "1/~10"]);
+    /// assert_eq!(frames.last(), Some(&"5/5"));
+    /// ```
+    ///
+    /// Without a hint, nothing is reported done before the end:
+    /// ```
+    /// use cpbar::testing::Capture;
+    /// use cpbar::*;
+    /// use std::sync::{Arc, Mutex};
+    ///
+    /// let reached = Arc::new(Mutex::new(Vec::new()));
+    /// let log = reached.clone();
+    /// let mut lines = "a\nb\nc".lines();
+    /// let capture = Capture::new();
+    /// let mut progress_bar = ProgressBar::new(std::iter::from_fn(move || lines.next()))
+    ///     .with_estimated_bounds()
+    ///     .with_template("{percent}% {pos}/{len}")
+    ///     .with_milestones(vec![50, 100], move |percent| log.lock().unwrap().push(percent))
+    ///     .with_draw_target(capture.clone());
+    /// progress_bar.next();
+    /// assert!(reached.lock().unwrap().is_empty());
+    /// capture.assert_last_frame("  ?% 0/?");
+    /// for _ in progress_bar {}
+    /// assert_eq!(*reached.lock().unwrap(), [50, 100]);
+    /// capture.assert_last_frame("100% 3/3");
+    ///
+    /// // Reaching the estimate is not the end yet either.
+    /// let mut more = vec![4, 5].into_iter();
+    /// let input = (0..4).chain(std::iter::from_fn(move || more.next()));
+    /// let capture = Capture::new();
+    /// let mut progress_bar = ProgressBar::new(input)
+    ///     .with_estimated_bounds()
+    ///     .with_template("{percent}% {pos}/{len}")
+    ///     .with_draw_target(capture.clone());
+    /// for _ in 0..5 {
+    ///     progress_bar.next();
+    /// }
+    /// capture.assert_last_frame(" 99% 4/~4");
+    /// ```
+    ///
+    pub fn with_estimated_bounds(self) -> ProgressBar<Iter, Estimated> {
+        let total = match self.iter.size_hint() {
+            (_, Some(upper)) => upper,
+            (lower, None) => lower,
+        };
+        let mut state = lock(&self.state);
+        state.set_length(total as u64, MAX_COLUMN_WIDTH);
+        if total == 0 {
+            // No hint at all: the total is unknown until the iterator ends.
+            state.length = None;
+        }
+        state.approximate = Some('~');
+        drop(state);
+        self.with_state(Estimated)
+    }
+
+    /// Turns the bar into a heartbeat: a spinner and the elapsed time, with no
+    /// position or count, for loops that only need to show they are alive.
+    /// The spinner advances whenever an item is requested and on every
+    /// [`ProgressBar::tick`] or [`ProgressHandle::tick`].
+    ///
+    /// # Example
+    /// ```
+    /// use cpbar::*;
+    /// use std::time::Duration;
+    ///
+    /// let poll = ProgressBar::new(std::iter::repeat(()).take(3))
+    ///     .with_heartbeat()
+    ///     .with_message("waiting for the server");
+    /// let handle = poll.handle();
+    /// for _ in poll {
+    ///     std::thread::sleep(Duration::from_millis(10));
+    ///     handle.tick();
+    /// }
+    /// ```
+    ///
+    pub fn with_heartbeat(self) -> Self {
+        lock(&self.state).template = Some(Template::parse(HEARTBEAT_TEMPLATE));
+        self
+    }
+}
+
+impl<Iter> ProgressBar<Skip<Iter>, Unbounded>
+where
+    Iter: Iterator,
+{
+    /// Resumes the progress saved to `path` by
+    /// [`ProgressHandle::save_checkpoint`]: the items processed by the previous
+    /// run are skipped, and the position, total and elapsed time carry on from
+    /// the checkpoint. Starts from scratch when there is no checkpoint yet.
+    /// Checkpoints count items, they are not meant for weighted bars.
+    ///
+    /// # Example
+    /// ```
+    /// use cpbar::*;
+    /// let path = std::env::temp_dir().join("cpbar-resume-example.checkpoint");
+    /// # let _ = std::fs::remove_file(&path);
+    ///
+    /// // First run, killed after 4 items.
+    /// let progress_bar = ProgressBar::resume_from(&path, 0..10)?.with_bounds();
+    /// let handle = progress_bar.handle();
+    /// for i in progress_bar {
+    ///     if i == 4 {
+    ///         break;
+    ///     }
+    ///     // process item...
+    ///     handle.save_checkpoint(&path)?;
+    /// }
+    ///
+    /// // Next run.
+    /// let progress_bar = ProgressBar::resume_from(&path, 0..10)?.with_bounds();
+    /// assert_eq!(progress_bar.position(), 4);
+    /// assert_eq!(progress_bar.length(), Some(10));
+    /// assert_eq!(progress_bar.collect::<Vec<_>>(), [4, 5, 6, 7, 8, 9]);
+    /// # std::fs::remove_file(&path)?;
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    ///
+    pub fn resume_from<P: AsRef<Path>>(path: P, iter: Iter) -> io::Result<Self> {
+        let checkpoint = Checkpoint::load(path.as_ref())?;
+        let skipped = checkpoint.map_or(0, |checkpoint| checkpoint.position);
+        let bar = Self::wrap(iter.skip(skipped as usize));
+        if let Some(checkpoint) = checkpoint {
+            let mut state = lock(&bar.state);
+            state.position = checkpoint.position;
+            state.carried = checkpoint.elapsed;
+            if let Some(total) = checkpoint.total {
+                state.set_length(total, total as usize);
+            }
+        }
+        Ok(bar)
     }
 }
+
 impl<Iter> ProgressBar<Iter, Unbounded>
 where
     Iter: ExactSizeIterator,
@@ -171,22 +2086,169 @@ where
     /// ```
     ///
     pub fn with_bounds(self) -> ProgressBar<Iter, Bounded> {
-        let bound = Bounded {
-            bound: self.iter.len(),
-            delims: ('[', ']'),
-        };
+        let mut state = lock(&self.state);
+        // A resumed bar already made progress over items no longer in `iter`.
+        let bound = state.position as usize + self.iter.len();
+        state.set_length(bound as u64, bound);
+        drop(state);
+        self.with_state(Bounded)
+    }
+}
+
+impl<It> ProgressBar<Chain<It>, Bounded>
+where
+    It: ExactSizeIterator,
+{
+    /// Creates a single bounded bar over several iterators consumed one after the
+    /// other. Its total is the sum of their lengths, so multi-input jobs show one
+    /// continuous percentage instead of a bar per input.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use cpbar::*;
+    /// let inputs = vec![vec![1, 2, 3], vec![4, 5], vec![6]];
+    /// let progress_bar = ProgressBar::chain(inputs.iter().map(|input| input.iter()));
+    /// assert_eq!(progress_bar.copied().sum::<i32>(), 21);
+    /// ```
+    ///
+    pub fn chain<I>(iters: I) -> Self
+    where
+        I: IntoIterator<Item = It>,
+    {
+        ProgressBar::new(Chain::new(iters)).with_bounds()
+    }
+}
+
+impl<Iter, Bound> ProgressBar<Iter, Bound>
+where
+    Iter: Iterator + Send + 'static,
+    Iter::Item: Send + 'static,
+{
+    /// Reads up to `depth` items ahead on a background thread while the
+    /// current item is processed, overlapping I/O with compute. The bar keeps
+    /// counting items as the loop receives them, and the `{queue}` template
+    /// field displays how many are waiting. Set the bounds before prefetching.
+    /// A panic of the inner iterator is resumed on the loop, see [`Prefetch`].
+    ///
+    /// # Example
+    /// ```
+    /// use cpbar::*;
+    /// let records = ProgressBar::new((0..8).map(|i| i * 2))
+    ///     .with_bounds()
+    ///     .with_prefetch(4)
+    ///     .with_template("{bar} {pos}/{len} ({queue} queued)");
+    /// assert_eq!(records.sum::<i32>(), 56);
+    /// ```
+    ///
+    pub fn with_prefetch(self, depth: usize) -> ProgressBar<Prefetch<Iter::Item>, Bound> {
+        let iter = Prefetch::new(self.iter, depth);
+        lock(&self.state).queue = Some(iter.queue());
         ProgressBar {
-            iter: self.iter,
-            start: std::time::Instant::now(),
-            bound,
-            index: self.index,
+            guard: self.guard,
+            iter,
+            bound: self.bound,
+            state: self.state,
+            ticker: self.ticker,
+            polling: self.polling,
+            batch: self.batch,
+        }
+    }
+}
+
+impl ProgressBar<Deadline, Timed> {
+    /// Creates a bar filling over `duration` whatever the work done, for
+    /// warm-ups, cool-downs or waiting for a deadline. It is redrawn by a
+    /// steady tick and finishes on its own once the time is up. Iterating it
+    /// yields the elapsed time until then.
+    ///
+    /// # Example
+    /// ```
+    /// use cpbar::*;
+    /// use std::time::Duration;
+    ///
+    /// let warm_up = ProgressBar::for_duration(Duration::from_millis(200));
+    /// while !warm_up.is_done() {
+    ///     // send warm-up requests...
+    ///     std::thread::sleep(Duration::from_millis(50));
+    /// }
+    /// assert_eq!(warm_up.percent(), Some(100.0));
+    ///
+    /// // Polling until the deadline, cancelling early once ready.
+    /// let mut wait = ProgressBar::for_duration(Duration::from_secs(30));
+    /// for elapsed in wait.by_ref() {
+    ///     if elapsed > Duration::from_millis(100) {
+    ///         break;
+    ///     }
+    ///     std::thread::sleep(Duration::from_millis(10));
+    /// }
+    /// wait.cancel();
+    /// assert!(wait.is_done());
+    ///
+    /// // Both the bar and the deadline follow the clock of the bar.
+    /// let clock = testing::MockClock::new();
+    /// let mut timer = ProgressBar::for_duration(Duration::from_secs(60))
+    ///     .with_clock(clock.clone())
+    ///     .with_draw_target(testing::Capture::new());
+    /// clock.advance(Duration::from_secs(15));
+    /// assert_eq!(timer.next(), Some(Duration::from_secs(15)));
+    /// assert_eq!(timer.percent(), Some(25.0));
+    /// clock.advance(Duration::from_secs(45));
+    /// assert_eq!(timer.next(), None);
+    /// assert!(timer.is_done());
+    ///
+    /// // The steady tick moves the bar like an item would, milestones included.
+    /// let reached = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+    /// let log = reached.clone();
+    /// let _timer = ProgressBar::for_duration(Duration::from_secs(3))
+    ///     .with_clock(clock.clone())
+    ///     .with_milestones([50], move |percent| log.lock().unwrap().push(percent))
+    ///     .with_draw_target(testing::Capture::new());
+    /// clock.advance(Duration::from_secs(2));
+    /// std::thread::sleep(Duration::from_millis(300));
+    /// assert_eq!(*reached.lock().unwrap(), [50]);
+    /// ```
+    ///
+    pub fn for_duration(duration: std::time::Duration) -> Self {
+        let deadline = Deadline::new(duration);
+        let timing = deadline.timing();
+        let bar = ProgressBar::wrap(deadline).with_state(Timed);
+        {
+            let mut state = lock(&bar.state);
+            state.timing = timing;
+            // Positions are milliseconds, precise enough for any bar width.
+            state.set_length(duration.as_millis() as u64, MAX_COLUMN_WIDTH);
+            state.timer = Some(duration);
+            state.template = Some(Template::parse("{percent}% {bar} {elapsed}"));
+            state.tick(0);
+        }
+        let interval = (duration / MAX_COLUMN_WIDTH as u32)
+            .clamp(std::time::Duration::from_millis(50), std::time::Duration::from_secs(1));
+        bar.enable_steady_tick(interval)
+    }
+
+    /// Whether the duration has passed or the bar was cancelled.
+    pub fn is_done(&self) -> bool {
+        let mut state = lock(&self.state);
+        if !state.finished && self.iter.is_done() {
+            state.refresh();
+        }
+        state.finished || self.iter.is_done()
+    }
+
+    /// Stops the bar before the duration has passed, leaving it where it is.
+    pub fn cancel(&mut self) {
+        self.iter.cancel();
+        lock(&self.state).finish();
+        if let Some(mut ticker) = self.ticker.take() {
+            ticker.stop();
         }
     }
 }
 
-impl<Iter> ProgressBar<Iter, Bounded>
+impl<Iter, Bound> ProgressBar<Iter, Bound>
 where
-    Iter: ExactSizeIterator,
+    Bound: ProgressBarBounded,
 {
     /// Adds custom delimetering chracters to bounded progress bar.
     ///
@@ -198,8 +2260,78 @@ where
     ///                 .with_delims(('<','>'));
     /// ```
     ///
-    pub fn with_delims(mut self, delims: (char, char)) -> Self {
-        self.bound.delims = delims;
+    pub fn with_delims(self, delims: (char, char)) -> Self {
+        lock(&self.state).delims = Some(delims);
+        self
+    }
+}
+
+impl<Iter, Bound, T, E> ProgressBar<Iter, Bound>
+where
+    Iter: Iterator<Item = Result<T, E>>,
+{
+    /// Tracks the `Err` items of an iterator over results. Failures are
+    /// counted in the `{errors}` template field and the bar turns red once one
+    /// occurs. Call it after the other type state changes such as `with_bounds`.
+    ///
+    /// # Example
+    /// ```
+    /// use cpbar::*;
+    /// # use std::io::Write;
+    /// # use std::sync::{Arc, Mutex};
+    /// # #[derive(Clone, Default)]
+    /// # struct Shared(Arc<Mutex<Vec<u8>>>);
+    /// # impl Write for Shared {
+    /// #     fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+    /// #         self.0.lock().unwrap().write(buf)
+    /// #     }
+    /// #     fn flush(&mut self) -> std::io::Result<()> {
+    /// #         Ok(())
+    /// #     }
+    /// # }
+    /// let sink = Shared::default();
+    /// let results = vec![Ok(1), Err("unreadable"), Ok(3)];
+    /// let progress_bar = ProgressBar::new(results.into_iter())
+    ///                 .with_bounds()
+    ///                 .with_template("{pos}/{len} ({errors} failed)")
+    ///                 .with_mirror(sink.clone(), std::time::Duration::ZERO)
+    ///                 .try_progress();
+    /// for result in progress_bar {
+    ///     // handle the result
+    /// }
+    /// let written = String::from_utf8(sink.0.lock().unwrap().clone()).unwrap();
+    /// assert_eq!(written.lines().last(), Some("3/3 (1 failed)"));
+    /// ```
+    ///
+    pub fn try_progress(self) -> ProgressBar<Iter, Fallible<Bound>> {
+        ProgressBar {
+            guard: self.guard,
+            iter: self.iter,
+            bound: Fallible { inner: self.bound },
+            state: self.state,
+            ticker: self.ticker,
+            polling: self.polling,
+            batch: self.batch,
+        }
+    }
+}
+
+impl<Iter, Bound> ProgressBar<Iter, Fallible<Bound>> {
+    /// Draws the failed items as a red segment at the start of the bar,
+    /// proportional to their share of the total, instead of tinting the whole bar.
+    ///
+    /// # Example
+    /// ```
+    /// use cpbar::*;
+    /// let results: Vec<Result<u32, String>> = vec![Ok(1), Err("bad".into()), Ok(3)];
+    /// let progress_bar = ProgressBar::new(results.into_iter())
+    ///                 .with_bounds()
+    ///                 .try_progress()
+    ///                 .with_failure_segment();
+    /// ```
+    ///
+    pub fn with_failure_segment(self) -> Self {
+        lock(&self.state).failure_segment = true;
         self
     }
 }
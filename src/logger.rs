@@ -0,0 +1,121 @@
+//!
+//! Adapter for the `log` crate. Available with the `log` feature.
+//!
+//! [`ProgressLogger`] wraps the logger of the application: records are
+//! forwarded to it unchanged, but it runs while the bars are erased, so
+//! `log::info!` calls made during iteration are printed above the bars
+//! instead of through them.
+//!
+//! ```
+//! use cpbar::*;
+//! use log::{Log, Metadata, Record};
+//!
+//! /// Logger printing `LEVEL message` lines to stderr.
+//! struct Stderr;
+//!
+//! impl Log for Stderr {
+//!     fn enabled(&self, _: &Metadata) -> bool {
+//!         true
+//!     }
+//!
+//!     fn log(&self, record: &Record) {
+//!         eprintln!("{} {}", record.level(), record.args());
+//!     }
+//!
+//!     fn flush(&self) {}
+//! }
+//!
+//! let progress_bar = ProgressBar::new((0..3)).with_bounds();
+//! progress_bar.handle().logger(Stderr).try_init(log::LevelFilter::Info).unwrap();
+//! for i in progress_bar {
+//!     log::info!("processing item {}", i);
+//! }
+//! ```
+//!
+use crate::writer::Target;
+use log::{LevelFilter, Log, Metadata, Record, SetLoggerError};
+
+/// Logger forwarding records to an inner [`Log`] implementation with the
+/// bars erased, the bars are redrawn below each record. Build it with
+/// [`ProgressHandle::logger`](crate::ProgressHandle::logger) or
+/// [`MultiProgress::logger`](crate::MultiProgress::logger).
+///
+/// Records the inner logger does not enable are dropped without touching
+/// the display.
+///
+/// # Example
+/// ```
+/// use cpbar::*;
+/// use log::{Log, Metadata, Record};
+/// use std::sync::Mutex;
+///
+/// #[derive(Default)]
+/// struct Collect(Mutex<Vec<String>>);
+///
+/// impl Log for Collect {
+///     fn enabled(&self, metadata: &Metadata) -> bool {
+///         metadata.level() <= log::Level::Info
+///     }
+///
+///     fn log(&self, record: &Record) {
+///         self.0.lock().unwrap().push(record.args().to_string());
+///     }
+///
+///     fn flush(&self) {}
+/// }
+///
+/// let progress_bar = ProgressBar::new((0..2)).with_bounds();
+/// let logger = progress_bar.handle().logger(Collect::default());
+/// for i in progress_bar {
+///     logger.log(&Record::builder().level(log::Level::Info).args(format_args!("{}", i)).build());
+///     logger.log(&Record::builder().level(log::Level::Debug).args(format_args!("x")).build());
+/// }
+/// assert_eq!(*logger.inner().0.lock().unwrap(), vec!["0", "1"]);
+/// ```
+pub struct ProgressLogger<L: Log> {
+    target: Target,
+    inner: L,
+}
+
+impl<L: Log> ProgressLogger<L> {
+    pub(crate) fn new(target: Target, inner: L) -> Self {
+        Self { target, inner }
+    }
+
+    /// The wrapped logger.
+    pub fn inner(&self) -> &L {
+        &self.inner
+    }
+
+    /// Installs the logger as the global logger of the `log` crate, with
+    /// `level` as the maximum level of the records logged.
+    pub fn try_init(self, level: LevelFilter) -> Result<(), SetLoggerError>
+    where
+        L: 'static,
+    {
+        log::set_boxed_logger(Box::new(self))?;
+        log::set_max_level(level);
+        Ok(())
+    }
+}
+
+impl<L: Log> Log for ProgressLogger<L> {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.inner.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.inner.enabled(record.metadata()) {
+            return;
+        }
+        let inner = &self.inner;
+        self.target.suspend(|| {
+            inner.log(record);
+            inner.flush();
+        });
+    }
+
+    fn flush(&self) {
+        self.inner.flush();
+    }
+}
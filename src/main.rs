@@ -1,4 +1,5 @@
 
+#[allow(clippy::useless_vec)]
 fn main() {
 
     let vector =  vec![24;50];
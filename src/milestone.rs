@@ -0,0 +1,79 @@
+//!
+//! Milestones: the percentages at which a bar reports its progress once,
+//! instead of redrawing on every item.
+//!
+
+/// Percentage between two milestones of the plain mode.
+pub(crate) const PLAIN_STEP: usize = 25;
+
+/// Tracks the milestones, every `step` percent, reached by a bar.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Milestones {
+    step: usize,
+    reached: usize,
+}
+
+impl Milestones {
+    pub(crate) fn new(step: usize) -> Self {
+        Self {
+            step: step.clamp(1, 100),
+            reached: 0,
+        }
+    }
+
+    /// Forgets the milestones reported, for a bar starting over.
+    pub(crate) fn reset(&mut self) {
+        self.reached = 0;
+    }
+
+    /// Milestones reached by `percent` that were not reported yet, in
+    /// increasing order. Progress jumping over several milestones reports
+    /// each of them, like the callbacks of `with_milestones`.
+    pub(crate) fn crossed(&mut self, percent: f64) -> Vec<usize> {
+        let milestone = (percent.clamp(0.0, 100.0) as usize / self.step) * self.step;
+        if milestone <= self.reached {
+            return Vec::new();
+        }
+        let crossed = (self.reached + self.step..=milestone).step_by(self.step).collect();
+        self.reached = milestone;
+        crossed
+    }
+}
+
+/// Callback invoked once for each of a set of percentages, see
+/// `with_milestones`.
+pub(crate) struct Notifier {
+    percents: Vec<u8>,
+    next: usize,
+    callback: Box<dyn FnMut(u8) + Send>,
+}
+
+impl Notifier {
+    pub(crate) fn new(percents: Vec<u8>, callback: Box<dyn FnMut(u8) + Send>) -> Self {
+        let mut percents: Vec<u8> = percents.into_iter().map(|percent| percent.min(100)).collect();
+        percents.sort_unstable();
+        percents.dedup();
+        Self {
+            percents,
+            next: 0,
+            callback,
+        }
+    }
+
+    /// Invokes the callback for every milestone reached by `percent` since the
+    /// previous call, in increasing order.
+    pub(crate) fn update(&mut self, percent: f64) {
+        while let Some(&milestone) = self.percents.get(self.next) {
+            if percent < milestone as f64 {
+                break;
+            }
+            self.next += 1;
+            (self.callback)(milestone);
+        }
+    }
+
+    /// Arms the milestones again, for a bar starting over.
+    pub(crate) fn reset(&mut self) {
+        self.next = 0;
+    }
+}
@@ -0,0 +1,385 @@
+//!
+//! Several progress bars drawn together, one per line.
+//!
+use crate::handle::ProgressHandle;
+use crate::format::DurationFormat;
+use crate::state::{lock, try_lock, BarState};
+use crate::stats::{Session, SessionStats};
+use crate::term::{erase_lines, RESTORE};
+use crate::writer::ProgressWriter;
+use crate::ProgressBar;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+
+/// Stable identifier of a progress bar, unique within the process.
+///
+/// # Example
+/// ```
+/// use cpbar::*;
+/// let first = ProgressBar::new(0..3);
+/// let second = ProgressBar::new(0..3);
+/// assert_ne!(first.id(), second.id());
+/// assert_eq!(first.id(), first.handle().id());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct BarId(u64);
+
+impl BarId {
+    pub(crate) fn next() -> Self {
+        static NEXT: AtomicU64 = AtomicU64::new(0);
+        BarId(NEXT.fetch_add(1, Ordering::Relaxed))
+    }
+
+    pub(crate) fn value(&self) -> u64 {
+        self.0
+    }
+}
+
+impl std::fmt::Display for BarId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "#{}", self.0)
+    }
+}
+
+/// Lines currently displayed by a multi-bar, in the order bars were added.
+pub(crate) struct MultiDraw {
+    lines: Vec<(BarId, Option<String>)>,
+    drawn: usize,
+    /// Set once torn down, the bars are no longer redrawn.
+    closed: bool,
+    /// Number of `suspend` calls running, the bars are not redrawn meanwhile.
+    suspended: usize,
+    /// Outcome of the bars finished so far.
+    pub(crate) session: Session,
+}
+
+impl MultiDraw {
+    fn new() -> Self {
+        Self {
+            lines: Vec::new(),
+            drawn: 0,
+            closed: false,
+            suspended: 0,
+            session: Session::default(),
+        }
+    }
+
+    /// Leaves the bars as they are, restores the terminal and stops drawing.
+    pub(crate) fn teardown(&mut self) {
+        self.closed = true;
+        self.drawn = 0;
+        print!("{}", RESTORE);
+        let _ = std::io::Write::flush(&mut std::io::stdout());
+    }
+
+    fn insert(&mut self, id: BarId) {
+        if !self.lines.iter().any(|(line, _)| *line == id) {
+            self.lines.push((id, None));
+        }
+    }
+
+    /// Updates the line of a bar and redraws every line.
+    pub(crate) fn update(&mut self, id: BarId, frame: String) {
+        match self.lines.iter_mut().find(|(line, _)| *line == id) {
+            Some((_, line)) => *line = Some(frame),
+            None => self.lines.push((id, Some(frame))),
+        }
+        self.redraw(None);
+    }
+
+    /// Prints a persistent message above all the bars.
+    pub(crate) fn println(&mut self, message: &str) {
+        self.redraw(Some(message));
+    }
+
+    /// Erases every bar and stops drawing them until `resume`.
+    fn pause(&mut self) {
+        if self.drawn > 0 {
+            print!("{}", erase_lines(self.drawn));
+            let _ = std::io::Write::flush(&mut std::io::stdout());
+            self.drawn = 0;
+        }
+        self.suspended += 1;
+    }
+
+    /// Draws the bars again once the last `suspend` call is over.
+    fn resume(&mut self) {
+        self.suspended -= 1;
+        self.redraw(None);
+    }
+
+    /// Removes the line of a bar.
+    pub(crate) fn remove(&mut self, id: BarId) {
+        self.lines.retain(|(line, _)| *line != id);
+        self.redraw(None);
+    }
+
+    fn redraw(&mut self, message: Option<&str>) {
+        if self.closed || self.suspended > 0 {
+            if let Some(message) = message {
+                println!("{}", message);
+            }
+            return;
+        }
+        let mut output = String::new();
+        if self.drawn > 0 {
+            output.push_str(&erase_lines(self.drawn));
+        }
+        if let Some(message) = message {
+            output.push_str(message);
+            output.push('\n');
+        }
+        self.drawn = 0;
+        for frame in self.lines.iter().filter_map(|(_, frame)| frame.as_ref()) {
+            output.push_str(frame);
+            output.push('\n');
+            self.drawn += 1;
+        }
+        print!("{}", output);
+    }
+}
+
+/// Erases every bar of `draw`, runs `f` and draws the bars again below its
+/// output. The display is not locked while `f` runs, so `f` can update the
+/// bars: their new state is drawn once `f` returns.
+pub(crate) fn suspend<R>(draw: &Mutex<MultiDraw>, f: impl FnOnce() -> R) -> R {
+    /// Resumes even if `f` panics.
+    struct Resume<'a>(&'a Mutex<MultiDraw>);
+    impl Drop for Resume<'_> {
+        fn drop(&mut self) {
+            lock(self.0).resume();
+        }
+    }
+
+    lock(draw).pause();
+    let _resume = Resume(draw);
+    f()
+}
+
+struct Registry {
+    bars: Vec<(BarId, Weak<Mutex<BarState>>)>,
+}
+
+/// Displays several progress bars at once, one per line, and keeps a registry
+/// of them so any part of the application can look a bar up by its [`BarId`].
+///
+/// # Example
+/// ```
+/// use cpbar::*;
+///
+/// let multi = MultiProgress::new();
+/// let downloads = multi.add(ProgressBar::new(0..4).with_bounds());
+/// let uploads = multi.add(ProgressBar::new(0..8).with_bounds());
+/// let id = uploads.id();
+///
+/// // Somewhere else, for instance in an event handler:
+/// let registry = multi.clone();
+/// let notify = move || {
+///     if let Some(handle) = registry.get(id) {
+///         handle.set_message("connection restored");
+///     }
+/// };
+///
+/// std::thread::scope(|scope| {
+///     scope.spawn(move || for _ in downloads {});
+///     scope.spawn(move || for _ in uploads {});
+/// });
+/// notify();
+/// ```
+#[derive(Clone)]
+pub struct MultiProgress {
+    registry: Arc<Mutex<Registry>>,
+    draw: Arc<Mutex<MultiDraw>>,
+    /// Width of the longest prefix, the prefixes of all bars are padded to it.
+    prefix_width: Arc<AtomicUsize>,
+}
+
+impl Default for MultiProgress {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MultiProgress {
+    /// Creates an empty multi-bar display.
+    pub fn new() -> Self {
+        Self {
+            registry: Arc::new(Mutex::new(Registry { bars: Vec::new() })),
+            draw: Arc::new(Mutex::new(MultiDraw::new())),
+            prefix_width: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    /// Attaches a bar to this display. The bar is drawn on its own line below
+    /// the bars added before it, and can be looked up through [`Self::get`].
+    /// Prefixes without a fixed width are padded to the longest one so the
+    /// bars line up.
+    pub fn add<Iter, Bound>(&self, bar: ProgressBar<Iter, Bound>) -> ProgressBar<Iter, Bound> {
+        self.attach(&bar.state);
+        bar
+    }
+
+    /// Attaches the state of a bar to this display.
+    pub(crate) fn attach(&self, state: &Arc<Mutex<BarState>>) {
+        let id = {
+            let mut state = lock(state);
+            state.target.multi = Some(Arc::clone(&self.draw));
+            state.aligned_prefix = Some(Arc::clone(&self.prefix_width));
+            let prefix = state.prefix.clone();
+            state.set_prefix(&prefix);
+            state.id
+        };
+        lock(&self.draw).insert(id);
+        lock(&self.registry)
+            .bars
+            .push((id, Arc::downgrade(state)));
+    }
+
+    /// Returns a handle to the bar with the given id, if it was added to this
+    /// display and is still alive.
+    pub fn get(&self, id: BarId) -> Option<ProgressHandle> {
+        let mut registry = lock(&self.registry);
+        registry.bars.retain(|(_, state)| state.strong_count() > 0);
+        registry
+            .bars
+            .iter()
+            .find(|(bar, _)| *bar == id)
+            .and_then(|(_, state)| state.upgrade())
+            .map(ProgressHandle::new)
+    }
+
+    /// Ids of the live bars, in the order they were added.
+    pub fn ids(&self) -> Vec<BarId> {
+        let mut registry = lock(&self.registry);
+        registry.bars.retain(|(_, state)| state.strong_count() > 0);
+        registry.bars.iter().map(|(id, _)| *id).collect()
+    }
+
+    /// Prints a persistent line above all the bars.
+    pub fn println(&self, message: &str) {
+        lock(&self.draw).println(message);
+    }
+
+    /// Wraps the writer of a logger so its lines are printed above all the
+    /// bars, see [`ProgressWriter`].
+    pub fn writer<W: std::io::Write>(&self, inner: W) -> ProgressWriter<W> {
+        ProgressWriter::for_multi(self.clone(), inner)
+    }
+
+    /// Wraps a logger of the `log` crate so its records are printed above
+    /// all the bars, see [`ProgressLogger`](crate::logger::ProgressLogger).
+    #[cfg(feature = "log")]
+    pub fn logger<L: log::Log>(&self, inner: L) -> crate::logger::ProgressLogger<L> {
+        crate::logger::ProgressLogger::new(crate::writer::Target::Multi(self.clone()), inner)
+    }
+
+    /// Installs a panic hook cleaning up the display before the panic message
+    /// is printed: the display stops drawing, the cursor and the scroll region
+    /// are restored and the message of the previously installed hook ends up
+    /// below the bars instead of in the middle of them. The bars themselves
+    /// keep running: those of threads surviving the panic still count their
+    /// items and finish as usual, without being drawn.
+    ///
+    /// The hook only keeps a weak reference to the display, once it is dropped
+    /// panics go straight to the previous hook.
+    ///
+    /// # Example
+    /// ```
+    /// use cpbar::*;
+    ///
+    /// let multi = MultiProgress::new();
+    /// multi.install_panic_hook();
+    /// let worker = multi.add(ProgressBar::new(0..10).with_bounds());
+    /// let survivor = multi.add(ProgressBar::new(0..3).with_bounds());
+    /// let crashed = std::thread::spawn(move || {
+    ///     for i in worker {
+    ///         if i == 5 {
+    ///             panic!("worker failed on item {}", i);
+    ///         }
+    ///     }
+    /// })
+    /// .join();
+    /// assert!(crashed.is_err());
+    ///
+    /// // The other bars still finish normally.
+    /// for _ in survivor {}
+    /// assert_eq!(multi.session().bars, 1);
+    /// ```
+    pub fn install_panic_hook(&self) {
+        let draw = Arc::downgrade(&self.draw);
+        let previous = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            if let Some(draw) = draw.upgrade() {
+                teardown(&draw);
+            }
+            previous(info);
+        }));
+    }
+
+    /// Statistics aggregated over the bars of this display that finished.
+    ///
+    /// # Example
+    /// ```
+    /// use cpbar::*;
+    ///
+    /// let multi = MultiProgress::new();
+    /// let small = multi.add(ProgressBar::new(0..4).with_bounds());
+    /// let large = multi.add(ProgressBar::new(0..8).with_bounds());
+    /// std::thread::scope(|scope| {
+    ///     scope.spawn(move || for _ in small {});
+    ///     scope.spawn(move || for _ in large {});
+    /// });
+    /// let session = multi.session();
+    /// assert_eq!(session.bars, 2);
+    /// assert_eq!(session.items, 12);
+    /// assert_eq!(session.total, 12);
+    /// multi.print_summary();
+    /// ```
+    pub fn session(&self) -> SessionStats {
+        lock(&self.draw).session.stats()
+    }
+
+    /// Prints a one line report of [`Self::session`] above the bars, for an
+    /// end-of-run summary of every task.
+    pub fn print_summary(&self) {
+        let session = self.session();
+        self.println(&format!(
+            "{} bars: {} items, {} total in {} (concurrency {:.1})",
+            session.bars,
+            session.items,
+            session.total,
+            DurationFormat::default().format(session.wall_time),
+            session.concurrency
+        ));
+    }
+
+    /// Erases all the bars, runs `f` and draws them again below its output.
+    /// The display is not locked while `f` runs: the bars can be updated and
+    /// lines printed from `f`, they show up once it returns. See
+    /// [`ProgressHandle::suspend`].
+    ///
+    /// # Example
+    /// ```
+    /// use cpbar::*;
+    ///
+    /// let multi = MultiProgress::new();
+    /// let bar = multi.add(ProgressBar::new(0..3).with_bounds());
+    /// let handle = bar.handle();
+    /// multi.suspend(|| {
+    ///     eprintln!("reconnecting...");
+    ///     handle.set_message("reconnected");
+    /// });
+    /// for _ in bar {}
+    /// ```
+    pub fn suspend<R>(&self, f: impl FnOnce() -> R) -> R {
+        suspend(&self.draw, f)
+    }
+}
+
+/// Closes the display, leaving the lifecycle of every bar alone.
+fn teardown(draw: &Mutex<MultiDraw>) {
+    match try_lock(draw) {
+        Some(mut draw) => draw.teardown(),
+        None => print!("\n{}", RESTORE),
+    }
+}
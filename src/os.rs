@@ -0,0 +1,122 @@
+//!
+//! Progress reported to the operating system through the terminal: the
+//! taskbar indicator driven by the `OSC 9;4` sequence (Windows Terminal,
+//! ConEmu, iTerm2) and the window title.
+//!
+
+/// Where a bar reports its progress outside of its own line, see
+/// [`ProgressBar::with_os_progress`](crate::ProgressBar::with_os_progress).
+/// Terminals that do not support a sequence ignore it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OsProgress {
+    /// Taskbar or tab indicator, through `OSC 9;4`. Unbounded bars show an
+    /// indeterminate indicator and bars with failed items an error state.
+    Taskbar,
+    /// Percentage in the terminal title, the previous title is restored once
+    /// the bar finishes.
+    Title,
+    /// Both the taskbar indicator and the title.
+    Both,
+}
+
+impl OsProgress {
+    fn taskbar(&self) -> bool {
+        matches!(self, OsProgress::Taskbar | OsProgress::Both)
+    }
+
+    fn title(&self) -> bool {
+        matches!(self, OsProgress::Title | OsProgress::Both)
+    }
+}
+
+/// States of the `OSC 9;4` sequence.
+const CLEAR: u8 = 0;
+const NORMAL: u8 = 1;
+const ERROR: u8 = 2;
+const INDETERMINATE: u8 = 3;
+
+/// Builds the OS progress sequences of a bar, only when what they show
+/// changes. The bar writes them to the console, never to custom targets.
+pub(crate) struct OsIndicator {
+    mode: OsProgress,
+    shown: Option<(u8, Option<usize>, String)>,
+}
+
+impl OsIndicator {
+    pub(crate) fn new(mode: OsProgress) -> Self {
+        Self { mode, shown: None }
+    }
+
+    /// Reports `percent` (`None` when unbounded), `failed` telling whether
+    /// some items failed and `label` being shown next to it in the title.
+    /// Control characters are dropped from the label, a BEL or ESC would end
+    /// the title sequence early. Returns the sequences to write, empty when
+    /// nothing changed.
+    pub(crate) fn update(&mut self, percent: Option<usize>, failed: bool, label: &str) -> String {
+        let state = match percent {
+            None => INDETERMINATE,
+            Some(_) if failed => ERROR,
+            Some(_) => NORMAL,
+        };
+        let label: String = label.chars().filter(|c| !c.is_control()).collect();
+        let current = (state, percent, label);
+        if self.shown.as_ref() == Some(&current) {
+            return String::new();
+        }
+        let mut sequences = String::new();
+        if self.shown.is_none() && self.mode.title() {
+            // Saves the title to restore it on finish.
+            sequences.push_str("\x1b[22;0t");
+        }
+        if self.mode.taskbar() {
+            sequences.push_str(&format!("\x1b]9;4;{};{}\x07", state, percent.unwrap_or(0)));
+        }
+        if self.mode.title() {
+            let title = match percent {
+                Some(percent) => format!("{}%", percent),
+                None => "…".to_string(),
+            };
+            match current.2.is_empty() {
+                true => sequences.push_str(&format!("\x1b]0;{}\x07", title)),
+                false => sequences.push_str(&format!("\x1b]0;{} {}\x07", title, current.2)),
+            }
+        }
+        self.shown = Some(current);
+        sequences
+    }
+
+    /// Sequences removing the indicator and restoring the title, empty when
+    /// nothing was shown.
+    pub(crate) fn clear(&mut self) -> String {
+        if self.shown.take().is_none() {
+            return String::new();
+        }
+        let mut sequences = String::new();
+        if self.mode.taskbar() {
+            sequences.push_str(&format!("\x1b]9;4;{};0\x07", CLEAR));
+        }
+        if self.mode.title() {
+            sequences.push_str("\x1b[23;0t");
+        }
+        sequences
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn control_characters_stay_out_of_the_title() {
+        let mut indicator = OsIndicator::new(OsProgress::Title);
+        let sequences = indicator.update(Some(50), false, "copy\x07\x1b]0;pwned\nfiles");
+        assert_eq!(sequences, "\x1b[22;0t\x1b]0;50% copy]0;pwnedfiles\x07");
+    }
+
+    #[test]
+    fn unchanged_label_writes_nothing() {
+        let mut indicator = OsIndicator::new(OsProgress::Both);
+        assert!(!indicator.update(Some(10), false, "a\x07").is_empty());
+        assert_eq!(indicator.update(Some(10), false, "a"), "");
+    }
+}
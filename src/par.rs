@@ -0,0 +1,193 @@
+//!
+//! Progress bars over rayon parallel iterators. Available with the `rayon` feature.
+//!
+//! Items are counted with the atomic counter behind
+//! [`ProgressHandle::inc`](crate::ProgressHandle::inc) as workers pick them up,
+//! and the frame is only drawn by whichever worker finds the bar idle, so
+//! workers never wait on each other to report progress. The bar finishes once
+//! the parallel iterator has been driven to completion.
+//!
+//! ```
+//! use cpbar::par::ParProgressIterator;
+//! use rayon::prelude::*;
+//!
+//! let sizes: Vec<u64> = (1..=1000).collect();
+//! let progress_bar = sizes.par_iter().progress().with_bounds();
+//! let handle = progress_bar.handle();
+//! let total: u64 = progress_bar.map(|size| size * 2).sum();
+//! assert_eq!(total, 1001000);
+//! assert_eq!(handle.position(), 1000);
+//! assert_eq!(handle.length(), Some(1000));
+//! ```
+//!
+use crate::state::lock;
+use crate::{Bounded, ProgressBar, ProgressBarWeight, Unbounded};
+use rayon::iter::plumbing::{Consumer, ProducerCallback, UnindexedConsumer};
+use rayon::iter::{IndexedParallelIterator, ParallelIterator};
+
+/// Parallel iterator driven by a [`ParProgressBar`].
+pub struct Parallel<P>(P);
+
+/// Progress bar over a parallel iterator. It is a [`ProgressBar`] whose inner
+/// sequence is driven by rayon instead of iterated.
+pub type ParProgressBar<P, Bound = Unbounded> = ProgressBar<Parallel<P>, Bound>;
+
+/// Adds `progress()` to every parallel iterator.
+///
+/// # Example
+/// ```
+/// use cpbar::par::ParProgressIterator;
+/// use rayon::prelude::*;
+///
+/// let lines: Vec<String> = (0..100).map(|i| i.to_string()).collect();
+/// let lengths: Vec<usize> = lines
+///     .par_iter()
+///     .progress()
+///     .with_bounds()
+///     .with_template("{bar} {pos}/{len}")
+///     .map(|line| line.len())
+///     .collect();
+/// assert_eq!(lengths[42], 2);
+/// ```
+pub trait ParProgressIterator: ParallelIterator {
+    /// Wraps the parallel iterator in an unbounded progress bar.
+    fn progress(self) -> ParProgressBar<Self>;
+}
+
+impl<P> ParProgressIterator for P
+where
+    P: ParallelIterator,
+{
+    fn progress(self) -> ParProgressBar<Self> {
+        ProgressBar::from_par_iter(self)
+    }
+}
+
+impl<P> ProgressBar<Parallel<P>, Unbounded>
+where
+    P: ParallelIterator,
+{
+    /// Creates a new progress bar over a parallel iterator.
+    pub fn from_par_iter(par: P) -> Self {
+        Self::wrap(Parallel(par))
+    }
+
+    /// Bounds a parallel bar with the number of items the iterator will
+    /// yield, for iterators whose length is not known upfront such as
+    /// `par_bridge()` or `filter()`.
+    pub fn with_length(self, length: usize) -> ParProgressBar<P, Bounded> {
+        lock(&self.state).set_length(length as u64, length);
+        self.with_state(Bounded)
+    }
+}
+
+impl<P> ProgressBar<Parallel<P>, Unbounded>
+where
+    P: IndexedParallelIterator,
+{
+    /// Initializes a parallel bar with the length of an indexed parallel iterator.
+    ///
+    /// # Example
+    /// ```
+    /// use cpbar::par::ParProgressIterator;
+    /// use rayon::prelude::*;
+    ///
+    /// let progress_bar = (0..600).into_par_iter().progress().with_bounds();
+    /// assert_eq!(progress_bar.length(), Some(600));
+    /// ```
+    pub fn with_bounds(self) -> ParProgressBar<P, Bounded> {
+        let length = self.iter.0.len();
+        self.with_length(length)
+    }
+}
+
+impl<P, Bound> ProgressBar<Parallel<P>, Bound>
+where
+    P: ParallelIterator,
+    Bound: ProgressBarWeight<P::Item> + Sync,
+{
+    /// Runs `drive` with the inner iterator and a function counting every
+    /// item it yields, then finishes the bar.
+    fn drive_counted<R, F>(self, drive: F) -> R
+    where
+        F: FnOnce(P, &(dyn Fn(P::Item) -> P::Item + Sync + Send)) -> R,
+    {
+        let handle = self.handle();
+        let ProgressBar {
+            guard,
+            iter,
+            bound,
+            state,
+            ticker,
+            ..
+        } = self;
+        let count = |item: P::Item| {
+            if bound.failed(&item) {
+                lock(&state).errors += 1;
+            }
+            handle.inc(bound.weight(&item));
+            item
+        };
+        let result = drive(iter.0, &count);
+        lock(&state).finish();
+        if let Some(mut ticker) = ticker {
+            ticker.stop();
+        }
+        drop(guard);
+        result
+    }
+}
+
+impl<P, Bound> ParallelIterator for ProgressBar<Parallel<P>, Bound>
+where
+    P: ParallelIterator,
+    Bound: ProgressBarWeight<P::Item> + Send + Sync,
+{
+    type Item = P::Item;
+
+    fn drive_unindexed<C>(self, consumer: C) -> C::Result
+    where
+        C: UnindexedConsumer<Self::Item>,
+    {
+        self.drive_counted(|par, count| par.map(count).drive_unindexed(consumer))
+    }
+
+    fn opt_len(&self) -> Option<usize> {
+        self.iter.0.opt_len()
+    }
+}
+
+/// Indexed adapters such as `enumerate()` or `zip()` keep working on the bar.
+///
+/// # Example
+/// ```
+/// use cpbar::par::ParProgressIterator;
+/// use rayon::prelude::*;
+///
+/// let names = vec!["serde", "rand", "log"];
+/// let numbered: Vec<(usize, &&str)> = names.par_iter().progress().enumerate().collect();
+/// assert_eq!(numbered, vec![(0, &"serde"), (1, &"rand"), (2, &"log")]);
+/// ```
+impl<P, Bound> IndexedParallelIterator for ProgressBar<Parallel<P>, Bound>
+where
+    P: IndexedParallelIterator,
+    Bound: ProgressBarWeight<P::Item> + Send + Sync,
+{
+    fn len(&self) -> usize {
+        self.iter.0.len()
+    }
+
+    fn drive<C>(self, consumer: C) -> C::Result
+    where
+        C: Consumer<Self::Item>,
+    {
+        self.drive_counted(|par, count| par.map(count).drive(consumer))
+    }
+
+    fn with_producer<CB>(self, callback: CB) -> CB::Output
+    where
+        CB: ProducerCallback<Self::Item>,
+    {
+        self.drive_counted(|par, count| par.map(count).with_producer(callback))
+    }
+}
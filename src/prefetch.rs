@@ -0,0 +1,137 @@
+//!
+//! Read-ahead of the items of an iterator on a background thread.
+//!
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+
+/// Iterator producing the items of another iterator on a background thread,
+/// up to `depth` items ahead of the consumer, so slow reads overlap with the
+/// processing of the current item. Built by
+/// [`ProgressBar::with_prefetch`](crate::ProgressBar::with_prefetch).
+///
+/// # Example
+/// ```
+/// use cpbar::*;
+///
+/// let lines = Prefetch::new((0..5).map(|i| format!("line {}", i)), 2);
+/// assert_eq!(lines.count(), 5);
+///
+/// // The producer blocks once `depth` items are waiting.
+/// let mut numbers = Prefetch::new(0..100, 2);
+/// numbers.next();
+/// std::thread::sleep(std::time::Duration::from_millis(50));
+/// assert_eq!(numbers.queued(), 2);
+/// ```
+///
+/// A panic of the inner iterator is resumed on the consumer once the items
+/// read before it are consumed, instead of ending the sequence early:
+/// ```
+/// use cpbar::*;
+///
+/// let numbers = (0..4).map(|i| if i == 2 { panic!("read failed") } else { i });
+/// let mut numbers = Prefetch::new(numbers, 1);
+/// assert_eq!(numbers.next(), Some(0));
+/// assert_eq!(numbers.next(), Some(1));
+/// let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| numbers.next()));
+/// assert!(result.is_err());
+/// ```
+pub struct Prefetch<Item> {
+    items: Receiver<Item>,
+    queued: Arc<Queue>,
+    remaining: (usize, Option<usize>),
+    /// Thread reading the inner iterator, joined once it stops sending.
+    producer: Option<JoinHandle<()>>,
+}
+
+/// Items sent by the producer and received by the consumer. Each side only
+/// counts what it completed, so the difference never includes an item the
+/// producer is still blocked on.
+#[derive(Debug, Default)]
+pub(crate) struct Queue {
+    sent: AtomicUsize,
+    received: AtomicUsize,
+}
+
+impl Queue {
+    /// Number of items waiting in the channel.
+    pub(crate) fn len(&self) -> usize {
+        // Read the consumer side first: it never gets ahead of the producer.
+        let received = self.received.load(Ordering::Acquire);
+        let sent = self.sent.load(Ordering::Acquire);
+        sent.saturating_sub(received)
+    }
+}
+
+impl<Item> Prefetch<Item>
+where
+    Item: Send + 'static,
+{
+    /// Starts reading `iter` ahead, keeping at most `depth` items waiting.
+    pub fn new<It>(iter: It, depth: usize) -> Self
+    where
+        It: Iterator<Item = Item> + Send + 'static,
+    {
+        let (sender, items) = mpsc::sync_channel(depth);
+        let queued = Arc::new(Queue::default());
+        let remaining = iter.size_hint();
+        let queue = Arc::clone(&queued);
+        let producer = thread::spawn(move || {
+            for item in iter {
+                if sender.send(item).is_err() {
+                    // The consumer is gone, stop reading.
+                    break;
+                }
+                queue.sent.fetch_add(1, Ordering::Release);
+            }
+        });
+        Self {
+            items,
+            queued,
+            remaining,
+            producer: Some(producer),
+        }
+    }
+}
+
+impl<Item> Prefetch<Item> {
+    /// Number of items read ahead and waiting to be consumed.
+    pub fn queued(&self) -> usize {
+        self.queued.len()
+    }
+
+    /// Shared counter of the items waiting, read by the bar state.
+    pub(crate) fn queue(&self) -> Arc<Queue> {
+        Arc::clone(&self.queued)
+    }
+}
+
+impl<Item> Iterator for Prefetch<Item> {
+    type Item = Item;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let item = self.items.recv().ok();
+        match item {
+            Some(_) => {
+                self.queued.received.fetch_add(1, Ordering::Release);
+                self.remaining.0 = self.remaining.0.saturating_sub(1);
+                self.remaining.1 = self.remaining.1.map(|upper| upper.saturating_sub(1));
+            }
+            None => {
+                self.remaining = (0, Some(0));
+                // The producer is done, it may have stopped on a panic.
+                if let Some(producer) = self.producer.take() {
+                    if let Err(panic) = producer.join() {
+                        std::panic::resume_unwind(panic);
+                    }
+                }
+            }
+        }
+        item
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.remaining
+    }
+}
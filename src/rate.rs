@@ -0,0 +1,250 @@
+//!
+//! Rate estimation: how fast a bar advances, used by the `{rate}` and `{eta}`
+//! fields.
+//!
+use std::collections::VecDeque;
+use std::time::Duration;
+
+/// How the rate of a bar is estimated from its history, see
+/// [`ProgressBar::with_rate_estimator`](crate::ProgressBar::with_rate_estimator).
+/// Without an estimator the rate is the average since the bar started.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RateEstimator {
+    /// Exponential moving average of the rate between two ticks: each new
+    /// sample weighs `alpha`, between 0 and 1, and the history `1 - alpha`.
+    /// Low values smooth bursts out, high values follow changes quickly.
+    Ema {
+        /// Weight of the latest sample.
+        alpha: f64,
+    },
+    /// Average rate over the last `secs` seconds, forgetting anything older.
+    SlidingWindow {
+        /// Length of the window.
+        secs: f64,
+    },
+}
+
+/// Rates below this many units per second are too slow to estimate anything
+/// from: a moving average decaying over a stall never reaches zero.
+pub(crate) const MIN_RATE: f64 = 1e-9;
+
+/// Number of slices a sliding window is cut in: samples closer than a slice
+/// are merged, so the memory of a window does not grow with the rate.
+const WINDOW_SLICES: u32 = 64;
+
+/// Rate of a sequence of positions, estimated with a [`RateEstimator`].
+///
+/// # Example
+/// A bursty workload: steady, then stalled, then a burst.
+/// ```
+/// use cpbar::{RateEstimator, RateTracker};
+/// use std::time::Duration;
+///
+/// let samples = [(0, 0), (1, 10), (2, 10), (3, 40)];
+/// let mut ema = RateTracker::new(RateEstimator::Ema { alpha: 0.5 });
+/// let mut window = RateTracker::new(RateEstimator::SlidingWindow { secs: 2.0 });
+/// for &(secs, position) in samples.iter() {
+///     ema.record(position, Duration::from_secs(secs));
+///     window.record(position, Duration::from_secs(secs));
+/// }
+/// // Rates of 10, 0 and 30 items per second, averaged with decreasing weights.
+/// assert_eq!(ema.rate(), 17.5);
+/// // 30 items over the last 2 seconds.
+/// assert_eq!(window.rate(), 15.0);
+/// ```
+///
+/// A stall drags the moving average down gradually, while the window forgets
+/// the earlier progress once it is older than the window:
+/// ```
+/// use cpbar::{RateEstimator, RateTracker};
+/// use std::time::Duration;
+///
+/// let mut ema = RateTracker::new(RateEstimator::Ema { alpha: 0.5 });
+/// let mut window = RateTracker::new(RateEstimator::SlidingWindow { secs: 2.0 });
+/// for (secs, position) in [(0, 0), (1, 100), (2, 100), (3, 100), (4, 100)].iter() {
+///     ema.record(*position, Duration::from_secs(*secs));
+///     window.record(*position, Duration::from_secs(*secs));
+/// }
+/// assert_eq!(ema.rate(), 12.5);
+/// assert_eq!(window.rate(), 0.0);
+/// ```
+#[derive(Debug, Clone)]
+pub struct RateTracker {
+    estimator: RateEstimator,
+    /// Last sample, for the moving average.
+    last: Option<(Duration, u64)>,
+    average: Option<f64>,
+    /// Samples of the window, oldest first, at most one per slice of the
+    /// window besides the latest.
+    window: VecDeque<(Duration, u64)>,
+}
+
+impl RateEstimator {
+    /// Whether the weight is within 0 to 1 or the window is neither empty nor
+    /// longer than a `Duration` can hold.
+    pub(crate) fn is_valid(&self) -> bool {
+        match *self {
+            RateEstimator::Ema { alpha } => (0.0..=1.0).contains(&alpha),
+            RateEstimator::SlidingWindow { secs } => {
+                secs > 0.0 && Duration::try_from_secs_f64(secs).is_ok()
+            }
+        }
+    }
+}
+
+impl RateTracker {
+    /// Creates a tracker without any sample.
+    pub fn new(estimator: RateEstimator) -> Self {
+        Self {
+            estimator,
+            last: None,
+            average: None,
+            window: VecDeque::new(),
+        }
+    }
+
+    /// Records that the sequence reached `position` after `elapsed`. Samples
+    /// must be recorded in chronological order.
+    pub fn record(&mut self, position: u64, elapsed: Duration) {
+        match self.estimator {
+            RateEstimator::Ema { alpha } => {
+                if let Some((time, previous)) = self.last {
+                    let secs = (elapsed.saturating_sub(time)).as_secs_f64();
+                    if secs <= 0.0 {
+                        // Same instant: merge with the previous sample.
+                        return;
+                    }
+                    let rate = position.saturating_sub(previous) as f64 / secs;
+                    let alpha = alpha.clamp(0.0, 1.0);
+                    self.average = Some(match self.average {
+                        Some(average) => alpha * rate + (1.0 - alpha) * average,
+                        None => rate,
+                    });
+                }
+                self.last = Some((elapsed, position));
+            }
+            RateEstimator::SlidingWindow { secs } => {
+                // A window too long for a `Duration` keeps every sample.
+                let length = Duration::try_from_secs_f64(secs.max(0.0)).unwrap_or(Duration::MAX);
+                if let Some(&(time, _)) = self.window.iter().rev().nth(1) {
+                    // Within the slice of the sample before: move the latest one forward.
+                    if elapsed.saturating_sub(time) < length / WINDOW_SLICES {
+                        self.window.pop_back();
+                    }
+                }
+                self.window.push_back((elapsed, position));
+                let start = elapsed.saturating_sub(length);
+                // Keep one sample at or before the start of the window as a baseline.
+                while self.window.len() > 2 && self.window[1].0 <= start {
+                    self.window.pop_front();
+                }
+            }
+        }
+    }
+
+    pub(crate) fn estimator(&self) -> RateEstimator {
+        self.estimator
+    }
+
+    /// Estimated units per second, 0 until two samples are recorded.
+    pub fn rate(&self) -> f64 {
+        match self.estimator {
+            RateEstimator::Ema { .. } => self.average.unwrap_or(0.0),
+            RateEstimator::SlidingWindow { .. } => {
+                match (self.window.front(), self.window.back()) {
+                    (Some(&(start, first)), Some(&(end, last))) if end > start => {
+                        last.saturating_sub(first) as f64 / (end - start).as_secs_f64()
+                    }
+                    _ => 0.0,
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ema_decays_below_min_rate_over_a_stall() {
+        let mut ema = RateTracker::new(RateEstimator::Ema { alpha: 0.5 });
+        ema.record(0, Duration::from_secs(0));
+        ema.record(10, Duration::from_secs(1));
+        for secs in 2..100 {
+            ema.record(10, Duration::from_secs(secs));
+        }
+        assert!(ema.rate() > 0.0);
+        assert!(ema.rate() < MIN_RATE);
+    }
+
+    #[test]
+    fn zero_progress_has_no_rate() {
+        for &estimator in [
+            RateEstimator::Ema { alpha: 0.3 },
+            RateEstimator::SlidingWindow { secs: 5.0 },
+        ]
+        .iter()
+        {
+            let mut tracker = RateTracker::new(estimator);
+            assert_eq!(tracker.rate(), 0.0);
+            for secs in 0..10 {
+                tracker.record(0, Duration::from_secs(secs));
+            }
+            assert_eq!(tracker.rate(), 0.0);
+        }
+    }
+
+    #[test]
+    fn samples_at_the_same_instant_are_merged() {
+        let mut ema = RateTracker::new(RateEstimator::Ema { alpha: 0.5 });
+        ema.record(0, Duration::from_secs(1));
+        ema.record(5, Duration::from_secs(1));
+        assert_eq!(ema.rate(), 0.0);
+        ema.record(10, Duration::from_secs(2));
+        assert_eq!(ema.rate(), 10.0);
+    }
+
+    #[test]
+    fn invalid_parameters_are_rejected() {
+        for &alpha in [-0.1, 1.1, f64::NAN, f64::INFINITY].iter() {
+            assert!(!RateEstimator::Ema { alpha }.is_valid(), "alpha {}", alpha);
+        }
+        for &secs in [0.0, -1.0, f64::NAN, f64::INFINITY, 1e300].iter() {
+            assert!(!RateEstimator::SlidingWindow { secs }.is_valid(), "secs {}", secs);
+        }
+        assert!(RateEstimator::Ema { alpha: 0.0 }.is_valid());
+        assert!(RateEstimator::Ema { alpha: 1.0 }.is_valid());
+        assert!(RateEstimator::SlidingWindow { secs: 0.5 }.is_valid());
+    }
+
+    #[test]
+    fn huge_window_keeps_every_sample() {
+        let mut tracker = RateTracker::new(RateEstimator::SlidingWindow { secs: 1e300 });
+        tracker.record(0, Duration::from_secs(0));
+        tracker.record(50, Duration::from_secs(10));
+        tracker.record(100, Duration::from_secs(20));
+        assert_eq!(tracker.rate(), 5.0);
+    }
+
+    #[test]
+    fn window_memory_does_not_grow_with_the_rate() {
+        let mut window = RateTracker::new(RateEstimator::SlidingWindow { secs: 5.0 });
+        // Ten seconds of ticks every 10µs.
+        for tick in 0..1_000_000 {
+            window.record(tick, Duration::from_micros(tick * 10));
+        }
+        assert!(window.window.len() <= WINDOW_SLICES as usize + 2);
+        assert!((window.rate() - 100_000.0).abs() < 1.0, "rate {}", window.rate());
+    }
+
+    #[test]
+    fn window_keeps_a_baseline_sample() {
+        let mut window = RateTracker::new(RateEstimator::SlidingWindow { secs: 2.0 });
+        for &(secs, position) in [(0, 0), (1, 10), (2, 10), (3, 40), (4, 60)].iter() {
+            window.record(position, Duration::from_secs(secs));
+        }
+        // From the sample at 2 seconds, exactly at the start of the window.
+        assert_eq!(window.rate(), 25.0);
+    }
+}
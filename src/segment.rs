@@ -0,0 +1,107 @@
+//!
+//! Stacked bars split in segments, one per class of items (succeeded,
+//! skipped, failed...), each taking a share of the bar proportional to its
+//! count.
+//!
+use crate::format::char_width;
+use crate::term::paint;
+
+/// A class of items drawn as its own region of a segmented bar, see
+/// [`ProgressBar::with_segments`](crate::ProgressBar::with_segments).
+///
+/// # Example
+/// ```
+/// use cpbar::Segment;
+///
+/// let retried = Segment::new("retried", '▒').with_color(35);
+/// assert_eq!(retried.name(), "retried");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Segment {
+    name: String,
+    fill: char,
+    color: Option<u8>,
+}
+
+impl Segment {
+    /// Segment drawn with `fill`, without color. Its count is available to
+    /// templates as the `{name}` field.
+    pub fn new(name: &str, fill: char) -> Self {
+        Self {
+            name: name.to_string(),
+            fill,
+            color: None,
+        }
+    }
+
+    /// Green segment named `ok`.
+    pub fn ok() -> Self {
+        Segment::new("ok", '█').with_color(32)
+    }
+
+    /// Yellow segment named `skipped`.
+    pub fn skipped() -> Self {
+        Segment::new("skipped", '█').with_color(33)
+    }
+
+    /// Red segment named `failed`.
+    pub fn failed() -> Self {
+        Segment::new("failed", '█').with_color(31)
+    }
+
+    /// Paints the segment with an ANSI foreground color code, `31` to `37`
+    /// for the standard colors or `90` to `97` for their bright variants.
+    pub fn with_color(mut self, code: u8) -> Self {
+        self.color = Some(code);
+        self
+    }
+
+    /// Name of the segment.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+/// Index of a segment in the list given to
+/// [`ProgressBar::with_segments`](crate::ProgressBar::with_segments).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SegmentId(pub usize);
+
+/// Renders the segments and their counts out of `total` on `columns` terminal
+/// columns, the cells left being drawn with `empty`. Region boundaries are
+/// computed from the cumulated counts so rounding never makes the bar drift.
+pub(crate) fn render(
+    segments: &[(Segment, u64)],
+    empty: char,
+    total: u64,
+    columns: usize,
+) -> String {
+    let cell_width = segments
+        .iter()
+        .map(|(segment, _)| char_width(segment.fill))
+        .chain(std::iter::once(char_width(empty)))
+        .max()
+        .unwrap_or(1)
+        .max(1);
+    let cells = columns / cell_width;
+    let mut bar = String::new();
+    let mut counted = 0;
+    let mut drawn = 0;
+    for (segment, count) in segments {
+        counted += count;
+        let end = (cells as u64 * counted.min(total))
+            .checked_div(total)
+            .unwrap_or(cells as u64) as usize;
+        if end > drawn {
+            let region: String = std::iter::repeat_n(segment.fill, end - drawn).collect();
+            match segment.color {
+                Some(code) => bar.push_str(&paint(&region, code)),
+                None => bar.push_str(&region),
+            }
+            drawn = end;
+        }
+    }
+    bar.extend(std::iter::repeat_n(empty, cells - drawn));
+    bar.extend(std::iter::repeat_n(' ', columns % cell_width));
+    bar
+}
@@ -0,0 +1,53 @@
+//!
+//! Terminal cleanup on Ctrl-C. Available with the `signals` feature on Unix.
+//!
+//! Interrupting a program kills it in the middle of a frame, which can leave a
+//! half-drawn bar, a hidden cursor or a shrunk scroll region behind. The
+//! handler installed by [`install_handler`] restores the terminal before the
+//! default action of `SIGINT` terminates the process:
+//!
+//! ```
+//! use cpbar::*;
+//!
+//! cpbar::signal::install_handler();
+//! for _ in ProgressBar::new(0..100).with_bounds() {
+//!     // interrupting now leaves a clean terminal
+//! }
+//! ```
+//!
+use std::os::raw::{c_int, c_void};
+use std::sync::Once;
+
+const SIGINT: c_int = 2;
+const SIG_DFL: usize = 0;
+
+/// Erases the current line, shows the cursor, resets the scroll region and
+/// removes the taskbar progress indicator.
+const RESTORE: &[u8] = b"\r\x1b[2K\x1b[?25h\x1b[r\x1b]9;4;0;0\x07";
+
+extern "C" {
+    fn signal(signum: c_int, handler: usize) -> usize;
+    fn raise(signum: c_int) -> c_int;
+    fn write(fd: c_int, buf: *const c_void, count: usize) -> isize;
+}
+
+static INSTALL: Once = Once::new();
+
+/// Installs a `SIGINT` handler restoring the terminal, then letting the default
+/// handler terminate the process with the usual status. Installing it more
+/// than once has no effect. It replaces any `SIGINT` handler installed before.
+pub fn install_handler() {
+    INSTALL.call_once(|| unsafe {
+        signal(SIGINT, on_interrupt as extern "C" fn(c_int) as usize);
+    });
+}
+
+/// Only calls async-signal-safe functions: the interrupted thread may hold
+/// the lock of a bar, so the bars themselves cannot be touched.
+extern "C" fn on_interrupt(signum: c_int) {
+    unsafe {
+        write(1, RESTORE.as_ptr() as *const c_void, RESTORE.len());
+        signal(signum, SIG_DFL);
+        raise(signum);
+    }
+}
@@ -0,0 +1,75 @@
+//!
+//! Secondary outputs that mirror the progress bar frames.
+//!
+use crate::format::strip_ansi;
+use crate::state::Timing;
+use std::io::Write;
+use std::time::{Duration, Instant};
+
+/// Mirrors frames to a slow sink such as a file, a socket or a log pipe.
+///
+/// Updates are coalesced: at most one frame is written per flush interval and
+/// frames produced in between are dropped in favor of the latest one, which is
+/// written once the interval elapsed, on the next update or tick of the bar,
+/// or when the bar finishes. Intervals are measured with the clock of the bar.
+pub(crate) struct Mirror {
+    sink: Box<dyn Write + Send>,
+    interval: Duration,
+    timing: Timing,
+    last_write: Option<Instant>,
+    /// Frame written last, not written again while it does not change.
+    written: Option<String>,
+    pending: Option<String>,
+}
+
+impl Mirror {
+    pub(crate) fn new(sink: Box<dyn Write + Send>, interval: Duration, timing: Timing) -> Self {
+        Self {
+            sink,
+            interval,
+            timing,
+            last_write: None,
+            written: None,
+            pending: None,
+        }
+    }
+
+    /// Records a new frame, writing it right away if the flush interval elapsed.
+    /// Colors are removed, sinks receive plain text.
+    pub(crate) fn update(&mut self, frame: &str) {
+        let frame = strip_ansi(frame);
+        if self.written.as_ref() == Some(&frame) {
+            self.pending = None;
+            return;
+        }
+        self.pending = Some(frame);
+        self.tick();
+    }
+
+    /// Writes the latest coalesced frame if the flush interval elapsed.
+    pub(crate) fn tick(&mut self) {
+        let now = self.timing.now();
+        let due = match self.last_write {
+            Some(last) => now.saturating_duration_since(last) >= self.interval,
+            None => true,
+        };
+        if due {
+            self.flush();
+        }
+    }
+
+    /// Writes the latest coalesced frame, if any.
+    pub(crate) fn flush(&mut self) {
+        if let Some(frame) = self.pending.take() {
+            self.write(&frame);
+            self.last_write = Some(self.timing.now());
+            self.written = Some(frame);
+        }
+    }
+
+    // Mirrors are best effort, a failing sink must not interrupt the iteration.
+    fn write(&mut self, frame: &str) {
+        let _ = writeln!(self.sink, "{}", frame);
+        let _ = self.sink.flush();
+    }
+}
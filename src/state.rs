@@ -0,0 +1,977 @@
+//!
+//! Progress state shared between a bar, its handles and its draw target.
+//!
+use crate::checkpoint::Checkpoint;
+use crate::draw::DrawTarget;
+use crate::error::ConfigError;
+use crate::ext::{Clock, Component, EtaEstimator, Observer, ProgressState, Renderer, SystemClock};
+use crate::format::{
+    char_width, compact, compact_level, display_width, fit, json_string, red, spoken, thousands,
+    unit_rate, DurationFormat, Units,
+};
+use crate::milestone::{Milestones, Notifier, PLAIN_STEP};
+use crate::multi::BarId;
+use crate::os::OsIndicator;
+use crate::prefetch::Queue;
+use crate::rate::{RateTracker, MIN_RATE};
+use crate::segment::{self, Segment};
+use crate::stats::{Recorder, Stats};
+use crate::styles::BarStyle;
+use crate::task::Phase;
+use crate::template::{
+    spinner, Template, Values, BOUNDED_TEMPLATE, COUNTER_TEMPLATE, UNBOUNDED_TEMPLATE,
+};
+use crate::term::CachedWidth;
+use std::cell::Cell;
+use std::fmt;
+use std::io::Write;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, TryLockError};
+use std::time::{Duration, Instant};
+
+pub(crate) const MAX_COLUMN_WIDTH: usize = 30;
+
+/// Highest percentage shown against an approximate total, which is only
+/// known to be reached once the sequence ends.
+const APPROXIMATE_PERCENT: u64 = 99;
+
+/// Everything needed to render a progress bar. Lives behind a mutex so handles
+/// and multi-bar displays can reach it while the bar is being iterated.
+pub(crate) struct BarState {
+    pub(crate) id: BarId,
+    pub(crate) index: usize,
+    /// Heartbeats since the bar started, each advancing the spinner, see
+    /// `heartbeat`.
+    pub(crate) beats: usize,
+    pub(crate) position: u64,
+    /// Weight of the item handed out last, accounted for on the next tick.
+    pub(crate) pending: u64,
+    /// Progress reported by handles from other threads, not yet drawn.
+    pub(crate) concurrent: Arc<AtomicU64>,
+    /// Clock of the bar and time it started, see `with_clock`.
+    pub(crate) timing: Timing,
+    /// Time spent in previous runs, see `resume_from`.
+    pub(crate) carried: Duration,
+    /// Elapsed time and position the rate and ETA are computed from, see
+    /// `reset_eta`.
+    pub(crate) eta_origin: (Duration, u64),
+    /// Total to reach, `None` for unbounded bars.
+    pub(crate) length: Option<u64>,
+    /// Duration of a bar filling with time, see `for_duration`.
+    pub(crate) timer: Option<Duration>,
+    /// Marker displayed before the total when it is only an estimate.
+    pub(crate) approximate: Option<char>,
+    /// Hook revising the estimated total.
+    pub(crate) estimator: Option<Estimator>,
+    /// Number of columns used by the bar itself.
+    pub(crate) columns: usize,
+    pub(crate) delims: Option<(char, char)>,
+    pub(crate) style: BarStyle,
+    /// Easing factor of the displayed position, see `with_smoothing`.
+    pub(crate) smoothing: Option<f64>,
+    /// Position displayed by the bar cells when smoothing.
+    pub(crate) shown: f64,
+    pub(crate) message: String,
+    /// Label displayed at the start of the line, see `with_prefix`.
+    pub(crate) prefix: String,
+    /// Columns reserved for the prefix, if fixed.
+    pub(crate) prefix_width: Option<usize>,
+    /// Widest prefix of the multi-bar display the bar belongs to.
+    pub(crate) aligned_prefix: Option<Arc<AtomicUsize>>,
+    /// Columns reserved for the message, if fixed.
+    pub(crate) message_width: Option<usize>,
+    /// Columns the line must fit in, the terminal width by default.
+    pub(crate) max_width: Option<usize>,
+    /// Width of the terminal, the line width without `max_width`.
+    terminal_width: CachedWidth,
+    pub(crate) template: Option<Template>,
+    /// Estimates the rate from recent progress instead of the overall average.
+    pub(crate) rate: Option<RateTracker>,
+    /// Custom template fields.
+    pub(crate) components: Vec<(String, Box<dyn Component>)>,
+    /// Replaces the default ETA computation.
+    pub(crate) eta_estimator: Option<Box<dyn EtaEstimator>>,
+    pub(crate) observers: Vec<Box<dyn Observer>>,
+    /// Replaces the template rendering and the console output.
+    pub(crate) renderer: Option<Box<dyn Renderer>>,
+    /// Applied to every frame before it is written.
+    pub(crate) frame_processor: Option<Box<dyn Fn(String) -> String + Send>>,
+    /// Number of items that failed, see `try_progress`.
+    pub(crate) errors: u64,
+    /// Draws the failures as their own segment instead of tinting the whole bar.
+    pub(crate) failure_segment: bool,
+    /// Stacked segments of the bar with their counts, see `with_segments`.
+    pub(crate) segments: Vec<(Segment, u64)>,
+    /// Progress reported to the taskbar or the title, see `with_os_progress`.
+    pub(crate) os: Option<OsIndicator>,
+    /// Items read ahead by a prefetching iterator.
+    pub(crate) queue: Option<Arc<Queue>>,
+    pub(crate) durations: DurationFormat,
+    pub(crate) units: Option<ScaledUnits>,
+    /// Name of the items counted, shown with thousands separators.
+    pub(crate) unit: Option<String>,
+    pub(crate) recorder: Recorder,
+    pub(crate) summary: bool,
+    /// Sink receiving the machine-readable summary record.
+    pub(crate) json_summary: Option<Box<dyn Write + Send>>,
+    /// Number of items the loop body reported as skipped.
+    pub(crate) skipped: u64,
+    /// Time spent in the tasks that ended, per name, see `task`.
+    pub(crate) phases: Vec<Phase>,
+    pub(crate) finished: bool,
+    /// Nothing is rendered while hidden, see `set_hidden`.
+    pub(crate) hidden: bool,
+    /// Reports progress as plain sentences at these milestones instead of
+    /// redrawing the line, see `with_plain_mode`.
+    pub(crate) plain: Option<Milestones>,
+    /// Callbacks run as the bar reaches percentages, see `with_milestones`.
+    pub(crate) notifiers: Vec<Notifier>,
+    pub(crate) target: DrawTarget,
+}
+
+impl BarState {
+    pub(crate) fn new() -> Self {
+        Self {
+            id: BarId::next(),
+            index: 0,
+            beats: 0,
+            position: 0,
+            pending: 0,
+            concurrent: Arc::new(AtomicU64::new(0)),
+            timing: Timing::new(Arc::new(SystemClock)),
+            carried: Duration::ZERO,
+            eta_origin: (Duration::ZERO, 0),
+            length: None,
+            timer: None,
+            approximate: None,
+            estimator: None,
+            columns: 0,
+            delims: Some(('[', ']')),
+            style: BarStyle::default(),
+            smoothing: None,
+            shown: 0.0,
+            message: String::new(),
+            prefix: String::new(),
+            prefix_width: None,
+            aligned_prefix: None,
+            message_width: None,
+            max_width: None,
+            terminal_width: CachedWidth::default(),
+            template: None,
+            rate: None,
+            components: Vec::new(),
+            eta_estimator: None,
+            observers: Vec::new(),
+            renderer: None,
+            frame_processor: None,
+            errors: 0,
+            failure_segment: false,
+            segments: Vec::new(),
+            os: None,
+            queue: None,
+            durations: DurationFormat::default(),
+            units: None,
+            unit: None,
+            recorder: Recorder::new(),
+            summary: false,
+            json_summary: None,
+            skipped: 0,
+            phases: Vec::new(),
+            finished: false,
+            hidden: hidden_by_env(),
+            plain: match env_flag(&["CPBAR_PLAIN"]) {
+                true => Some(Milestones::new(PLAIN_STEP)),
+                false => None,
+            },
+            notifiers: Vec::new(),
+            target: DrawTarget::new(),
+        }
+    }
+
+    /// Wraps the state so it can be shared with handles.
+    pub(crate) fn shared(self) -> Arc<Mutex<BarState>> {
+        Arc::new(Mutex::new(self))
+    }
+
+    /// Makes the bar bounded, restarting its clock.
+    pub(crate) fn set_length(&mut self, length: u64, columns: usize) {
+        self.length = Some(length);
+        self.columns = columns.min(MAX_COLUMN_WIDTH);
+        self.timing.restart();
+    }
+
+    /// Changes the total of a running bar, keeping its clock.
+    pub(crate) fn change_length(&mut self, length: u64) {
+        self.length = Some(length);
+        self.columns = (length as usize).min(MAX_COLUMN_WIDTH);
+        self.approximate = None;
+        self.estimator = None;
+    }
+
+    /// Starts the bar over for another pass: no progress, a new clock and new
+    /// estimations, the look and the total are kept.
+    pub(crate) fn reset(&mut self) {
+        self.position = 0;
+        self.beats = 0;
+        self.pending = 0;
+        self.concurrent.store(0, Ordering::Relaxed);
+        self.timing.restart();
+        self.carried = Duration::ZERO;
+        self.shown = 0.0;
+        self.errors = 0;
+        self.skipped = 0;
+        for (_, count) in &mut self.segments {
+            *count = 0;
+        }
+        let limit = self.recorder.limit();
+        self.recorder = Recorder::new();
+        if let Some(limit) = limit {
+            self.recorder.set_limit(limit);
+        }
+        if let Some(plain) = &mut self.plain {
+            plain.reset();
+        }
+        for notifier in &mut self.notifiers {
+            notifier.reset();
+        }
+        self.reset_eta();
+        self.finished = false;
+        self.target.reset();
+        self.draw();
+    }
+
+    /// Forgets the speed measured so far: the rate and the ETA only account
+    /// for the progress made from now on.
+    pub(crate) fn reset_eta(&mut self) {
+        self.eta_origin = (self.elapsed(), self.current_position());
+        if let Some(tracker) = &mut self.rate {
+            *tracker = RateTracker::new(tracker.estimator());
+        }
+    }
+
+    /// Makes the bar approximately bounded, the total being revised by `estimator`.
+    pub(crate) fn set_estimator(&mut self, estimator: Estimator) {
+        self.columns = MAX_COLUMN_WIDTH;
+        self.approximate = Some('≈');
+        self.estimator = Some(estimator);
+    }
+
+    /// Registers a new item being requested, `advance` being the weight of the
+    /// item processed since the previous tick, and draws the bar.
+    pub(crate) fn tick(&mut self, advance: u64) {
+        self.recorder.tick(self.timing.now());
+        self.position += advance + self.concurrent.swap(0, Ordering::Relaxed);
+        self.update();
+        self.index += 1;
+    }
+
+    /// Moves a timed bar to the current time, updates the estimations and
+    /// the milestones, draws the bar and notifies the observers. Returns
+    /// whether the time of a timed bar is up.
+    fn update(&mut self) -> bool {
+        let expired = self.sync_timer();
+        let elapsed = self.since_start();
+        if let Some(tracker) = &mut self.rate {
+            tracker.record(self.position, elapsed);
+        }
+        if let Some(estimator) = &mut self.estimator {
+            if self.index.is_multiple_of(estimator.every) {
+                // An estimate of nothing tells nothing about the total.
+                if let Some(total) = (estimator.revise)(self.position).filter(|&total| total > 0) {
+                    self.length = Some(total);
+                }
+            }
+        }
+        if let (Some(_), Some(total)) = (self.approximate, self.length) {
+            // The sequence outlived the estimate, leave some room ahead.
+            if self.position > total {
+                self.length = Some(self.position + (self.position / 2).max(1));
+            }
+        }
+        if self.eta_estimator.is_some() {
+            let snapshot = self.snapshot();
+            if let Some(estimator) = &mut self.eta_estimator {
+                estimator.update(&snapshot);
+            }
+        }
+        self.notify_milestones();
+        self.draw();
+        if !self.observers.is_empty() {
+            let snapshot = self.snapshot();
+            for observer in &mut self.observers {
+                observer.on_tick(&snapshot);
+            }
+        }
+        expired
+    }
+
+    /// Advances the spinner and redraws the bar. Unlike `tick` nothing is
+    /// counted: no item, timing sample, rate sample or milestone.
+    pub(crate) fn heartbeat(&mut self) {
+        self.beats += 1;
+        self.draw();
+    }
+
+    /// Redraws the bar between ticks, finishing a timed bar once its time is
+    /// up, and writes the frames the mirrors coalesced.
+    pub(crate) fn refresh(&mut self) {
+        if self.timer.is_none() {
+            self.draw();
+        } else if self.update() {
+            // Time is the progress of a timed bar, it advances like on a tick.
+            return self.finish();
+        }
+        self.target.tick_mirrors();
+    }
+
+    /// Moves a timed bar to the current time, returns whether the time is up.
+    fn sync_timer(&mut self) -> bool {
+        match self.timer {
+            Some(duration) => {
+                let elapsed = self.since_start().min(duration);
+                self.position = elapsed.as_millis() as u64;
+                elapsed >= duration
+            }
+            None => false,
+        }
+    }
+
+    pub(crate) fn draw(&mut self) {
+        if self.hidden {
+            return;
+        }
+        if self.plain.is_some() {
+            return self.report_milestones();
+        }
+        if self.os.is_some() {
+            let percent = self.percent().map(|percent| percent as usize);
+            let failed = self.errors > 0;
+            let label = match self.prefix.is_empty() {
+                true => self.message.clone(),
+                false => self.prefix.clone(),
+            };
+            if let Some(os) = &mut self.os {
+                let sequences = os.update(percent, failed, &label);
+                self.target.control(&sequences);
+            }
+        }
+        if let Some(factor) = self.smoothing {
+            let target = self.current_position() as f64;
+            self.shown += (target - self.shown) * factor.clamp(f64::MIN_POSITIVE, 1.0);
+            // Close enough, stop easing.
+            if (target - self.shown).abs() < 0.01 {
+                self.shown = target;
+            }
+        }
+        if self.renderer.is_some() {
+            let snapshot = self.snapshot();
+            let mut out = Vec::new();
+            if let Some(renderer) = &mut self.renderer {
+                // Rendering is best effort, like the console output.
+                let _ = renderer.render(&snapshot, &mut out);
+            }
+            return self.draw_rendered(out);
+        }
+        let mut frame = self.render();
+        if let Some(process) = &self.frame_processor {
+            frame = process(frame);
+        }
+        // Lines never wrap on the terminal, nor exceed an explicit width.
+        if self.max_width.is_some() || self.target.backend.is_none() {
+            let width = self.line_width();
+            if display_width(&frame) > width {
+                frame = fit(&frame, width);
+            }
+        }
+        self.target.draw(self.id, frame);
+    }
+
+    /// Draws the output of a custom renderer as a frame, nothing written
+    /// keeping the previous one.
+    fn draw_rendered(&mut self, out: Vec<u8>) {
+        let frame = String::from_utf8_lossy(&out);
+        let frame = frame.strip_suffix('\n').unwrap_or(&frame);
+        if !frame.is_empty() {
+            self.target.draw(self.id, frame.to_string());
+        }
+    }
+
+    /// Removes the OS progress indicator, if it was shown.
+    fn clear_os(&mut self) {
+        if let Some(os) = &mut self.os {
+            let sequences = os.clear();
+            self.target.control(&sequences);
+        }
+    }
+
+    /// Runs the callbacks of the milestones reached, whether or not the bar
+    /// is drawn.
+    fn notify_milestones(&mut self) {
+        if self.notifiers.is_empty() {
+            return;
+        }
+        if let Some(percent) = self.percent() {
+            for notifier in &mut self.notifiers {
+                notifier.update(percent);
+            }
+        }
+    }
+
+    /// Prints a sentence for every new milestone the bar reached, without any
+    /// control character so screen readers can follow.
+    fn report_milestones(&mut self) {
+        let percent = match self.percent() {
+            Some(percent) => percent,
+            None => return,
+        };
+        let crossed = match &mut self.plain {
+            Some(plain) => plain.crossed(percent),
+            None => return,
+        };
+        for milestone in crossed {
+            self.report_milestone(milestone);
+        }
+    }
+
+    fn report_milestone(&mut self, milestone: usize) {
+        let mut sentence = match self.prefix.is_empty() {
+            true => String::new(),
+            false => format!("{}: ", self.prefix),
+        };
+        sentence.push_str(&format!("{}% complete", milestone));
+        match self.eta(self.per_second()) {
+            _ if milestone == 100 => {
+                sentence.push_str(&format!(", took {}", spoken(self.elapsed())))
+            }
+            Some(eta) if eta < Duration::from_secs(1) => {
+                sentence.push_str(", less than a second remaining")
+            }
+            Some(eta) => sentence.push_str(&format!(", about {} remaining", spoken(eta))),
+            None => {}
+        }
+        sentence.push('.');
+        self.target.println(&sentence);
+    }
+
+    /// Renders the current frame.
+    pub(crate) fn render(&self) -> String {
+        let mut values = self.values();
+        let default;
+        let template = match &self.template {
+            Some(template) => template,
+            None => {
+                let source = match (self.length, &self.unit) {
+                    (Some(_), _) => BOUNDED_TEMPLATE,
+                    (None, Some(_)) => COUNTER_TEMPLATE,
+                    (None, None) => UNBOUNDED_TEMPLATE,
+                };
+                default = match self.prefix.is_empty() {
+                    true => Template::parse(source),
+                    false => Template::parse(&format!("{{prefix}} {}", source)),
+                };
+                &default
+            }
+        };
+        if template.is_elastic() {
+            values.line_width = self.line_width();
+        }
+        template.render(&values, self.durations)
+    }
+
+    /// Columns a line can use: the maximum width of the bar, or the width of
+    /// the terminal.
+    fn line_width(&self) -> usize {
+        self.max_width
+            .unwrap_or_else(|| self.terminal_width.get(self.timing.now()))
+    }
+
+    fn values(&self) -> Values {
+        let elapsed = self.elapsed();
+        let per_second = self.per_second();
+        let level = compact_level(self.length.unwrap_or(0).max(self.position));
+        let mut values = Values {
+            percent: None,
+            bar: String::new(),
+            pos: compact(self.position, level),
+            len: self
+                .length
+                .map_or_else(|| "?".to_string(), |len| compact(len, level)),
+            elapsed,
+            eta: None,
+            rate: format!("{:.2}/s", per_second),
+            message: match self.message_width {
+                Some(width) => fit(&self.message, width),
+                None => self.message.clone(),
+            },
+            errors: self.errors,
+            custom: Vec::new(),
+            spinner: spinner(self.index.saturating_sub(1) + self.beats),
+            unit: self.unit.clone().unwrap_or_default(),
+            line_width: 0,
+            prefix: match (self.prefix_width, &self.aligned_prefix) {
+                (Some(width), _) => fit(&self.prefix, width),
+                (None, Some(aligned)) => fit(&self.prefix, aligned.load(Ordering::Relaxed)),
+                (None, None) => self.prefix.clone(),
+            },
+            queue: self
+                .queue
+                .as_ref()
+                .map_or(0, |queue| queue.len()),
+        };
+        if let Some(unit) = &self.unit {
+            values.pos = thousands(self.position);
+            if let Some(len) = self.length {
+                values.len = thousands(len);
+            }
+            values.rate = unit_rate(per_second, unit);
+        }
+        if let Some(units) = &self.units {
+            values.pos = units.units.scale(self.position as f64, &units.pos);
+            if let Some(len) = self.length {
+                values.len = units.units.scale(len as f64, &units.len);
+            }
+            values.rate = format!(
+                "{}/s",
+                units.units.scale(per_second, &units.rate)
+            );
+        }
+        if let (Some(marker), Some(_)) = (self.approximate, self.length) {
+            values.len.insert(0, marker);
+        }
+        if let Some(total) = self.length {
+            let position = self.position.min(total);
+            let mut percent = (position * 100).checked_div(total).unwrap_or(100);
+            if self.approximate.is_some() {
+                percent = percent.min(APPROXIMATE_PERCENT);
+            }
+            // Wide delimiters take their extra columns from the bar, so lines
+            // stay aligned whatever the delimiters.
+            let columns = match self.delims {
+                Some((open, close)) => self
+                    .columns
+                    .saturating_sub(char_width(open).saturating_sub(1))
+                    .saturating_sub(char_width(close).saturating_sub(1)),
+                None => self.columns,
+            };
+            let mut bar = match self.smoothing {
+                _ if !self.segments.is_empty() => {
+                    segment::render(&self.segments, self.style.empty(), total, columns)
+                }
+                Some(_) if total > 0 => {
+                    const SCALE: u64 = 1_000_000;
+                    let shown = (self.shown / total as f64).clamp(0.0, 1.0);
+                    self.style.render((shown * SCALE as f64) as u64, SCALE, columns)
+                }
+                _ => self.style.render(position, total, columns),
+            };
+            if self.errors > 0 && self.segments.is_empty() {
+                bar = if self.failure_segment {
+                    let (failed, rest): (String, String) = {
+                        let chars: Vec<char> = bar.chars().collect();
+                        let failed = (chars.len() as u64 * self.errors.min(position))
+                            .checked_div(total)
+                            .unwrap_or(0) as usize;
+                        let split = failed.min(chars.len());
+                        (chars[..split].iter().collect(), chars[split..].iter().collect())
+                    };
+                    format!("{}{}", red(&failed), rest)
+                } else {
+                    red(&bar)
+                };
+            }
+            values.percent = Some(percent as usize);
+            values.bar = match self.delims {
+                Some((open, close)) => format!("{}{}{}", open, bar, close),
+                None => bar,
+            };
+            values.eta = self.eta(per_second);
+        }
+        if self.eta_estimator.is_some() || !self.components.is_empty() {
+            let snapshot = self.snapshot();
+            if let Some(estimator) = &self.eta_estimator {
+                values.eta = estimator.eta(&snapshot);
+            }
+            values.custom = self
+                .components
+                .iter()
+                .map(|(name, component)| (name.clone(), component.render(&snapshot)))
+                .collect();
+        }
+        values.custom.extend(
+            self.segments
+                .iter()
+                .map(|(segment, count)| (segment.name().to_string(), count.to_string())),
+        );
+        values
+    }
+
+    /// Called when the bar is dropped before finishing: leaves the terminal
+    /// clean without the reports printed by `finish`.
+    pub(crate) fn abandon(&mut self) {
+        if self.finished {
+            return;
+        }
+        self.finished = true;
+        self.clear_os();
+        self.target.abandon();
+    }
+
+    /// Called once the inner iterator is exhausted.
+    pub(crate) fn finish(&mut self) {
+        if self.finished {
+            return;
+        }
+        self.finished = true;
+        self.position += self.concurrent.swap(0, Ordering::Relaxed);
+        // Now that the sequence is over the total is known exactly.
+        let exact = self.approximate.take().is_some();
+        if exact {
+            self.length = Some(self.position);
+        }
+        self.notify_milestones();
+        let lagging = self.smoothing.is_some() && self.shown != self.position as f64;
+        if lagging {
+            self.shown = self.position as f64;
+        }
+        if exact || lagging {
+            self.draw();
+        }
+        self.clear_os();
+        if self.plain.is_some() && self.length.is_none() && !self.hidden {
+            let done = format!(
+                "{}Done, {} items in {}.",
+                match self.prefix.is_empty() {
+                    true => String::new(),
+                    false => format!("{}: ", self.prefix),
+                },
+                thousands(self.position),
+                spoken(self.elapsed())
+            );
+            self.target.println(&done);
+        }
+        if self.renderer.is_some() && !self.hidden {
+            let snapshot = self.snapshot();
+            let mut out = Vec::new();
+            if let Some(renderer) = &mut self.renderer {
+                let _ = renderer.finish(&snapshot, &mut out);
+            }
+            self.draw_rendered(out);
+        }
+        self.target.finish(self.id);
+        if !self.observers.is_empty() {
+            let snapshot = self.snapshot();
+            for observer in &mut self.observers {
+                observer.on_finish(&snapshot);
+            }
+        }
+        if let Some(multi) = &self.target.multi {
+            let items = self.stats().count as u64;
+            lock(multi)
+                .session
+                .record(items, self.position, self.timing.start(), self.timing.now());
+        }
+        if self.summary && !self.hidden {
+            let stats = self.stats();
+            let summary = format!(
+                "{} items: min {} / max {} / mean {} / p95 {}",
+                stats.count,
+                self.durations.format(stats.min),
+                self.durations.format(stats.max),
+                self.durations.format(stats.mean),
+                self.durations.format(stats.p95)
+            );
+            self.target.println(&summary);
+            if !self.phases.is_empty() {
+                let phases: Vec<String> = self
+                    .phases
+                    .iter()
+                    .map(|phase| format!("{} {}", phase.name, self.durations.format(phase.total)))
+                    .collect();
+                let phases = format!("phases: {}", phases.join(" / "));
+                self.target.println(&phases);
+            }
+        }
+        if let Some(mut sink) = self.json_summary.take() {
+            let _ = writeln!(sink, "{}", self.json_record());
+            let _ = sink.flush();
+        }
+    }
+
+    /// Single line JSON record describing the outcome of the bar.
+    pub(crate) fn json_record(&self) -> String {
+        let stats = self.stats();
+        let total = match self.length {
+            Some(length) => length.to_string(),
+            None => "null".to_string(),
+        };
+        format!(
+            concat!(
+                "{{\"id\":{},\"items\":{},\"position\":{},\"total\":{},",
+                "\"elapsed_secs\":{:.6},\"mean_secs\":{:.6},\"min_secs\":{:.6},",
+                "\"max_secs\":{:.6},\"p95_secs\":{:.6},\"failures\":{},",
+                "\"skipped\":{},\"message\":{}}}"
+            ),
+            self.id.value(),
+            stats.count,
+            self.position,
+            total,
+            self.elapsed().as_secs_f64(),
+            stats.mean.as_secs_f64(),
+            stats.min.as_secs_f64(),
+            stats.max.as_secs_f64(),
+            stats.p95.as_secs_f64(),
+            self.errors,
+            self.skipped,
+            json_string(&self.message)
+        )
+    }
+
+    /// Sets the label of the bar, widening the alignment of its display.
+    pub(crate) fn set_prefix(&mut self, prefix: &str) {
+        self.prefix = prefix.to_string();
+        if let Some(aligned) = &self.aligned_prefix {
+            aligned.fetch_max(display_width(prefix), Ordering::Relaxed);
+        }
+    }
+
+    /// Checks the configuration for settings that cannot work.
+    pub(crate) fn validate(&self) -> Result<(), ConfigError> {
+        if !self.style.shows_progress() {
+            return Err(ConfigError::EmptyCharset);
+        }
+        if self.message_width == Some(0) {
+            return Err(ConfigError::ZeroWidth("message"));
+        }
+        if self.prefix_width == Some(0) {
+            return Err(ConfigError::ZeroWidth("prefix"));
+        }
+        if self.max_width == Some(0) {
+            return Err(ConfigError::ZeroWidth("line"));
+        }
+        if let Some(factor) = self.smoothing {
+            if !(factor > 0.0 && factor <= 1.0) {
+                return Err(ConfigError::InvalidSmoothing(factor));
+            }
+        }
+        if let Some(tracker) = &self.rate {
+            if !tracker.estimator().is_valid() {
+                return Err(ConfigError::InvalidRateEstimator(tracker.estimator()));
+            }
+        }
+        if self.recorder.limit() == Some(0) {
+            return Err(ConfigError::ZeroSampleLimit);
+        }
+        Ok(())
+    }
+
+    /// View of the state handed to extensions.
+    pub(crate) fn snapshot(&self) -> ProgressState {
+        let rate = self.per_second();
+        ProgressState {
+            id: self.id,
+            position: self.current_position(),
+            length: self.length,
+            elapsed: self.elapsed(),
+            rate,
+            eta: self.eta(rate),
+            errors: self.errors,
+            message: self.message.clone(),
+            finished: self.finished,
+        }
+    }
+
+    /// Units of progress per second, from the rate estimator if there is one.
+    fn per_second(&self) -> f64 {
+        let (elapsed, position) = self.eta_origin;
+        match &self.rate {
+            Some(tracker) => tracker.rate(),
+            None => rate(
+                self.position.saturating_sub(position),
+                self.elapsed().saturating_sub(elapsed),
+            ),
+        }
+    }
+
+    /// Built-in estimate of the time left, `None` for unbounded bars or
+    /// before any progress was made.
+    fn eta(&self, per_second: f64) -> Option<Duration> {
+        let total = self.length?;
+        let position = self.position.min(total);
+        match &self.rate {
+            Some(_) if per_second > MIN_RATE => {
+                Duration::try_from_secs_f64((total - position) as f64 / per_second).ok()
+            }
+            Some(_) => None,
+            None => {
+                let (elapsed, origin) = self.eta_origin;
+                let done = position.saturating_sub(origin);
+                match done {
+                    0 => None,
+                    done => Duration::try_from_secs_f64(
+                        self.elapsed().saturating_sub(elapsed).as_secs_f64()
+                            * ((total - position) as f64 / done as f64),
+                    )
+                    .ok(),
+                }
+            }
+        }
+    }
+
+    /// Progress to save, see `save_checkpoint`.
+    pub(crate) fn checkpoint(&self) -> Checkpoint {
+        Checkpoint {
+            // Saved from the loop body: the current item is done.
+            position: self.current_position() + self.pending,
+            total: self.length,
+            elapsed: self.elapsed(),
+        }
+    }
+
+    /// Time since the bar started, including previous runs.
+    pub(crate) fn elapsed(&self) -> Duration {
+        self.since_start() + self.carried
+    }
+
+    /// Time since the bar started, in this run.
+    fn since_start(&self) -> Duration {
+        self.timing.elapsed()
+    }
+
+    /// Progress made so far, including the progress reported by other threads.
+    pub(crate) fn current_position(&self) -> u64 {
+        self.position + self.concurrent.load(Ordering::Relaxed)
+    }
+
+    /// Completion of a bounded bar, between 0 and 100. Below 100 as long as
+    /// the total is approximate.
+    pub(crate) fn percent(&self) -> Option<f64> {
+        let percent = self.length.map(|total| match total {
+            0 => 100.0,
+            total => self.current_position().min(total) as f64 * 100.0 / total as f64,
+        })?;
+        match self.approximate {
+            Some(_) => Some(percent.min(APPROXIMATE_PERCENT as f64)),
+            None => Some(percent),
+        }
+    }
+
+    pub(crate) fn stats(&self) -> Stats {
+        self.recorder.stats()
+    }
+}
+
+/// Revises the estimated total of a bar every `every` items.
+pub(crate) struct Estimator {
+    pub(crate) every: usize,
+    pub(crate) revise: Box<dyn FnMut(u64) -> Option<u64> + Send>,
+}
+
+/// Units of the numeric fields, with the prefix each of them used last.
+pub(crate) struct ScaledUnits {
+    units: Units,
+    pos: Cell<usize>,
+    len: Cell<usize>,
+    rate: Cell<usize>,
+}
+
+impl ScaledUnits {
+    pub(crate) fn new(units: Units) -> Self {
+        Self {
+            units,
+            pos: Cell::new(0),
+            len: Cell::new(0),
+            rate: Cell::new(0),
+        }
+    }
+}
+
+/// Whether the environment asks for progress bars to be hidden: either
+/// `CPBAR_HIDDEN` or `NO_PROGRESS` is set to anything but an empty string or `0`.
+fn hidden_by_env() -> bool {
+    env_flag(&["CPBAR_HIDDEN", "NO_PROGRESS"])
+}
+
+/// Whether one of the environment variables is set to anything but an empty
+/// string or `0`.
+fn env_flag(names: &[&str]) -> bool {
+    names.iter().any(|name| {
+        std::env::var_os(name).is_some_and(|value| !value.is_empty() && value != "0")
+    })
+}
+
+/// Units of progress per second.
+fn rate(position: u64, elapsed: Duration) -> f64 {
+    let secs = elapsed.as_secs_f64();
+    if secs > 0.0 {
+        position as f64 / secs
+    } else {
+        0.0
+    }
+}
+
+/// Clock of a bar and the time the bar started. A timed bar shares it with
+/// its [`Deadline`](crate::Deadline), so both follow the clock set with
+/// `with_clock` and start over together.
+#[derive(Clone)]
+pub(crate) struct Timing(Arc<Mutex<(Arc<dyn Clock>, Instant)>>);
+
+impl Timing {
+    /// Starts now according to `clock`.
+    pub(crate) fn new(clock: Arc<dyn Clock>) -> Self {
+        let start = clock.now();
+        Self(Arc::new(Mutex::new((clock, start))))
+    }
+
+    pub(crate) fn clock(&self) -> Arc<dyn Clock> {
+        Arc::clone(&lock(&self.0).0)
+    }
+
+    /// Measures time with `clock` from now on, starting over.
+    pub(crate) fn set_clock(&self, clock: Arc<dyn Clock>) {
+        let start = clock.now();
+        *lock(&self.0) = (clock, start);
+    }
+
+    pub(crate) fn now(&self) -> Instant {
+        lock(&self.0).0.now()
+    }
+
+    pub(crate) fn start(&self) -> Instant {
+        lock(&self.0).1
+    }
+
+    /// Starts over at the current time.
+    pub(crate) fn restart(&self) {
+        let mut timing = lock(&self.0);
+        timing.1 = timing.0.now();
+    }
+
+    /// Time since the start.
+    pub(crate) fn elapsed(&self) -> Duration {
+        let timing = lock(&self.0);
+        timing.0.now().saturating_duration_since(timing.1)
+    }
+}
+
+impl fmt::Debug for Timing {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Timing")
+            .field("elapsed", &self.elapsed())
+            .finish()
+    }
+}
+
+/// Locks a shared state. A panic while drawing must not disable the bar, so
+/// poisoning is ignored.
+pub(crate) fn lock<T>(shared: &Mutex<T>) -> MutexGuard<'_, T> {
+    shared.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Locks without blocking: the panicking thread may already hold the lock.
+pub(crate) fn try_lock<T>(shared: &Mutex<T>) -> Option<MutexGuard<'_, T>> {
+    match shared.try_lock() {
+        Ok(guard) => Some(guard),
+        Err(TryLockError::Poisoned(poisoned)) => Some(poisoned.into_inner()),
+        Err(TryLockError::WouldBlock) => None,
+    }
+}
@@ -0,0 +1,169 @@
+//!
+//! Per-item timing statistics.
+//!
+use std::time::{Duration, Instant};
+
+/// Timing statistics of the items processed by a progress bar.
+///
+/// Each sample is the time elapsed between two consecutive items being requested
+/// from the bar, which is the time spent processing an item in the loop body.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Stats {
+    /// Number of items timed.
+    pub count: usize,
+    /// Shortest time spent on a single item.
+    pub min: Duration,
+    /// Longest time spent on a single item.
+    pub max: Duration,
+    /// Average time spent per item.
+    pub mean: Duration,
+    /// 95th percentile of the time spent per item. Approximated when the samples
+    /// are limited through `with_sample_limit`.
+    pub p95: Duration,
+}
+
+/// Statistics aggregated over every finished bar of a
+/// [`MultiProgress`](crate::MultiProgress), see
+/// [`MultiProgress::session`](crate::MultiProgress::session).
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct SessionStats {
+    /// Number of bars finished.
+    pub bars: usize,
+    /// Number of items processed by all the bars.
+    pub items: u64,
+    /// Sum of the final positions of the bars, the total number of bytes for
+    /// bars weighted by size.
+    pub total: u64,
+    /// Time from the start of the first bar to the end of the last one.
+    pub wall_time: Duration,
+    /// Average number of bars running at the same time: the combined running
+    /// time of the bars over the wall time.
+    pub concurrency: f64,
+}
+
+/// Accumulates the outcome of the bars of a multi-bar display.
+#[derive(Default)]
+pub(crate) struct Session {
+    bars: usize,
+    items: u64,
+    total: u64,
+    busy: Duration,
+    first_start: Option<Instant>,
+    last_end: Option<Instant>,
+}
+
+impl Session {
+    /// Accounts for a bar that just finished.
+    pub(crate) fn record(&mut self, items: u64, position: u64, start: Instant, now: Instant) {
+        self.bars += 1;
+        self.items += items;
+        self.total += position;
+        self.busy += now.saturating_duration_since(start);
+        self.first_start = Some(self.first_start.map_or(start, |first| first.min(start)));
+        self.last_end = Some(self.last_end.map_or(now, |last| last.max(now)));
+    }
+
+    pub(crate) fn stats(&self) -> SessionStats {
+        let wall_time = match (self.first_start, self.last_end) {
+            (Some(start), Some(end)) => end.saturating_duration_since(start),
+            _ => Duration::ZERO,
+        };
+        let concurrency = match wall_time.as_secs_f64() {
+            secs if secs > 0.0 => self.busy.as_secs_f64() / secs,
+            _ => 0.0,
+        };
+        SessionStats {
+            bars: self.bars,
+            items: self.items,
+            total: self.total,
+            wall_time,
+            concurrency,
+        }
+    }
+}
+
+/// Records the time between ticks.
+pub(crate) struct Recorder {
+    last_tick: Option<Instant>,
+    count: usize,
+    total: Duration,
+    min: Duration,
+    max: Duration,
+    samples: Vec<Duration>,
+    limit: Option<usize>,
+    seed: u64,
+}
+
+impl Recorder {
+    pub(crate) fn new() -> Self {
+        Self {
+            last_tick: None,
+            count: 0,
+            total: Duration::ZERO,
+            min: Duration::MAX,
+            max: Duration::ZERO,
+            samples: Vec::new(),
+            limit: None,
+            seed: 0x2545_f491_4f6c_dd1d,
+        }
+    }
+
+    /// Keeps at most `limit` samples for the percentile computation using
+    /// reservoir sampling. Minimum, maximum and mean remain exact.
+    pub(crate) fn limit(&self) -> Option<usize> {
+        self.limit
+    }
+
+    pub(crate) fn set_limit(&mut self, limit: usize) {
+        self.limit = Some(limit);
+        self.samples.truncate(limit);
+    }
+
+    /// Registers a tick, timing the item processed since the previous one.
+    pub(crate) fn tick(&mut self, now: Instant) {
+        if let Some(last) = self.last_tick.replace(now) {
+            self.record(now - last);
+        }
+    }
+
+    fn record(&mut self, sample: Duration) {
+        self.count += 1;
+        self.total += sample;
+        self.min = self.min.min(sample);
+        self.max = self.max.max(sample);
+        match self.limit {
+            Some(limit) if self.samples.len() >= limit => {
+                // Algorithm R: replace a random sample with probability limit / count.
+                let slot = (self.next_random() % self.count as u64) as usize;
+                if slot < limit {
+                    self.samples[slot] = sample;
+                }
+            }
+            _ => self.samples.push(sample),
+        }
+    }
+
+    // xorshift64, good enough to pick reservoir slots.
+    fn next_random(&mut self) -> u64 {
+        self.seed ^= self.seed << 13;
+        self.seed ^= self.seed >> 7;
+        self.seed ^= self.seed << 17;
+        self.seed
+    }
+
+    pub(crate) fn stats(&self) -> Stats {
+        if self.count == 0 {
+            return Stats::default();
+        }
+        let mut sorted = self.samples.clone();
+        sorted.sort_unstable();
+        let rank = (sorted.len() * 95).div_ceil(100);
+        Stats {
+            count: self.count,
+            min: self.min,
+            max: self.max,
+            mean: Duration::from_nanos((self.total.as_nanos() / self.count as u128) as u64),
+            p95: sorted.get(rank.max(1) - 1).copied().unwrap_or_default(),
+        }
+    }
+}
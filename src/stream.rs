@@ -0,0 +1,138 @@
+//!
+//! Progress bars over asynchronous streams. Available with the `async` feature.
+//!
+//! [`Stream`] is the `futures::Stream` trait re-exported from `futures-core`,
+//! so the streams of `futures`, `tokio-stream` or any async runtime can be
+//! wrapped as they are. The bar ticks every time a new item is polled for,
+//! and offers the same type states and builder methods as bars over
+//! iterators.
+//!
+//! ```
+//! use cpbar::stream::{ProgressStream, Stream};
+//! use std::pin::Pin;
+//! use std::task::{Context, Poll};
+//! # use std::future::Future;
+//! # use std::sync::Arc;
+//! # use std::task::Wake;
+//! # struct Noop;
+//! # impl Wake for Noop {
+//! #     fn wake(self: Arc<Self>) {}
+//! # }
+//!
+//! /// Yields `Pending` once before each number.
+//! struct Numbers {
+//!     next: u32,
+//!     ready: bool,
+//! }
+//!
+//! impl Stream for Numbers {
+//!     type Item = u32;
+//!     fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<u32>> {
+//!         if !self.ready {
+//!             self.ready = true;
+//!             cx.waker().wake_by_ref();
+//!             return Poll::Pending;
+//!         }
+//!         self.ready = false;
+//!         self.next += 1;
+//!         Poll::Ready(if self.next <= 5 { Some(self.next) } else { None })
+//!     }
+//! }
+//!
+//! let mut bar = ProgressStream::from_stream(Numbers { next: 0, ready: false })
+//!     .with_length(5)
+//!     .with_template("{bar} {pos}/{len}");
+//! # let waker = Arc::new(Noop).into();
+//! # let mut cx = Context::from_waker(&waker);
+//! let mut sum = 0;
+//! loop {
+//!     match Pin::new(&mut bar).poll_next(&mut cx) {
+//!         Poll::Ready(Some(number)) => sum += number,
+//!         Poll::Ready(None) => break,
+//!         Poll::Pending => continue,
+//!     }
+//! }
+//! assert_eq!(sum, 15);
+//! assert_eq!(bar.stats().count, 5);
+//! ```
+//!
+use crate::state::lock;
+use crate::{Bounded, ProgressBar, ProgressBarWeight, Unbounded};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+pub use futures_core::Stream;
+
+/// Progress bar over a [`Stream`]. It is a [`ProgressBar`] whose inner
+/// sequence is polled instead of iterated.
+pub type ProgressStream<S, Bound = Unbounded> = ProgressBar<S, Bound>;
+
+impl<S> ProgressBar<S, Unbounded>
+where
+    S: Stream,
+{
+    /// Creates a new progress bar over an asynchronous stream.
+    ///
+    /// # Example
+    /// ```
+    /// use cpbar::stream::ProgressStream;
+    /// use futures::stream::{self, StreamExt};
+    ///
+    /// let bar = ProgressStream::from_stream(stream::iter(1..=4)).with_length(4);
+    /// let handle = bar.handle();
+    /// let doubled: Vec<u32> = futures::executor::block_on(bar.map(|n| n * 2).collect());
+    /// assert_eq!(doubled, vec![2, 4, 6, 8]);
+    /// assert_eq!(handle.position(), 4);
+    /// ```
+    pub fn from_stream(stream: S) -> Self {
+        Self::wrap(stream)
+    }
+
+    /// Bounds a stream bar with the number of items the stream will yield.
+    ///
+    /// # Example
+    /// ```
+    /// # use cpbar::stream::{ProgressStream, Stream};
+    /// # use std::pin::Pin;
+    /// # use std::task::{Context, Poll};
+    /// # struct Empty;
+    /// # impl Stream for Empty {
+    /// #     type Item = ();
+    /// #     fn poll_next(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Option<()>> {
+    /// #         Poll::Ready(None)
+    /// #     }
+    /// # }
+    /// let bar = ProgressStream::from_stream(Empty).with_length(10).with_delims(('<', '>'));
+    /// ```
+    pub fn with_length(self, length: usize) -> ProgressBar<S, Bounded> {
+        lock(&self.state).set_length(length as u64, length);
+        self.with_state(Bounded)
+    }
+}
+
+impl<S, Bound> Stream for ProgressBar<S, Bound>
+where
+    S: Stream + Unpin,
+    Bound: ProgressBarWeight<S::Item> + Unpin,
+{
+    type Item = S::Item;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let bar = self.get_mut();
+        if !bar.polling {
+            bar.request();
+            bar.polling = true;
+        }
+        let item = match Pin::new(&mut bar.iter).poll_next(cx) {
+            Poll::Ready(item) => item,
+            Poll::Pending => return Poll::Pending,
+        };
+        bar.polling = false;
+        bar.receive(item.as_ref());
+        Poll::Ready(item)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
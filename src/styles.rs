@@ -0,0 +1,206 @@
+//!
+//! Bar styles: the characters used to draw the bar, plus a gallery of
+//! ready-made presets.
+//!
+//! Rendered lines for each preset:
+//!
+//! ```
+//! use cpbar::*;
+//! # use std::io::Write;
+//! # use std::sync::{Arc, Mutex};
+//! # #[derive(Clone, Default)]
+//! # struct Shared(Arc<Mutex<Vec<u8>>>);
+//! # impl Write for Shared {
+//! #     fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+//! #         self.0.lock().unwrap().write(buf)
+//! #     }
+//! #     fn flush(&mut self) -> std::io::Result<()> {
+//! #         Ok(())
+//! #     }
+//! # }
+//! # fn frames(preset: Preset, template: Option<&str>) -> Vec<String> {
+//! #     let sink = Shared::default();
+//! #     let mut bar = ProgressBar::new(0..4).with_bounds().with_preset(preset);
+//! #     if let Some(template) = template {
+//! #         bar = bar.with_template(template);
+//! #     }
+//! #     for _ in bar.with_mirror(sink.clone(), std::time::Duration::ZERO) {}
+//! #     let written = String::from_utf8(sink.0.lock().unwrap().clone()).unwrap();
+//! #     written.lines().map(String::from).collect()
+//! # }
+//! let template = Some("{bar} {pos}/{len}");
+//! assert_eq!(
+//!     frames(Preset::Classic, template),
+//!     ["[░░░░] 0/4", "[▓░░░] 1/4", "[▓▓░░] 2/4", "[▓▓▓░] 3/4", "[▓▓▓▓] 4/4"]
+//! );
+//! assert_eq!(
+//!     frames(Preset::Ascii, template),
+//!     ["[    ] 0/4", "[=>  ] 1/4", "[==> ] 2/4", "[===>] 3/4", "[====] 4/4"]
+//! );
+//! assert_eq!(
+//!     frames(Preset::Blocks, template),
+//!     ["│    │ 0/4", "│█   │ 1/4", "│██  │ 2/4", "│███ │ 3/4", "│████│ 4/4"]
+//! );
+//! assert_eq!(
+//!     frames(Preset::Braille, template),
+//!     ["⠀⠀⠀⠀ 0/4", "⣿⠀⠀⠀ 1/4", "⣿⣿⠀⠀ 2/4", "⣿⣿⣿⠀ 3/4", "⣿⣿⣿⣿ 4/4"]
+//! );
+//! assert_eq!(
+//!     frames(Preset::Minimal, None),
+//!     ["  0%", " 25%", " 50%", " 75%", "100%"]
+//! );
+//! ```
+//!
+use crate::format::char_width;
+
+/// Characters used to draw a bar.
+///
+/// A style is built from a charset: the first character draws completed
+/// cells, the last one draws empty cells and the characters in between are
+/// partially filled cells, from the fullest to the emptiest. They give the bar
+/// a finer resolution than one cell.
+///
+/// # Example
+/// ```
+/// use cpbar::BarStyle;
+///
+/// let blocks = BarStyle::new("█▌ ");
+/// assert_eq!(blocks.render(5, 10, 4), "██  ");
+/// assert_eq!(blocks.render(7, 10, 4), "██▌ ");
+///
+/// let arrow = BarStyle::new("= ").with_head('>');
+/// assert_eq!(arrow.render(5, 10, 10), "=====>    ");
+/// assert_eq!(arrow.render(10, 10, 10), "==========");
+///
+/// let squares = BarStyle::new("🟩⬜");
+/// assert_eq!(squares.render(5, 10, 8), "🟩🟩⬜⬜");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BarStyle {
+    fill: char,
+    partials: Vec<char>,
+    empty: char,
+    head: Option<char>,
+}
+
+impl Default for BarStyle {
+    fn default() -> Self {
+        BarStyle::new("▓░")
+    }
+}
+
+impl BarStyle {
+    /// Creates a style from a charset, see [`BarStyle`]. Charsets of less than
+    /// two characters use a space for the missing ones.
+    pub fn new(charset: &str) -> Self {
+        let mut chars: Vec<char> = charset.chars().collect();
+        let fill = if chars.is_empty() {
+            ' '
+        } else {
+            chars.remove(0)
+        };
+        let empty = chars.pop().unwrap_or(' ');
+        Self {
+            fill,
+            partials: chars,
+            empty,
+            head: None,
+        }
+    }
+
+    /// Draws the given character right after the completed cells, `=>` style.
+    pub fn with_head(mut self, head: char) -> Self {
+        self.head = Some(head);
+        self
+    }
+
+    /// Renders `position` out of `total` on `columns` terminal columns. Wide
+    /// characters such as emoji take two columns each, so they get half as
+    /// many cells.
+    pub fn render(&self, position: u64, total: u64, columns: usize) -> String {
+        let cell_width = self.cell_width();
+        let mut bar = self.render_cells(position, total, columns / cell_width);
+        bar.extend(std::iter::repeat_n(' ', columns % cell_width));
+        bar
+    }
+
+    /// Character of the empty cells.
+    pub(crate) fn empty(&self) -> char {
+        self.empty
+    }
+
+    /// Whether filled and empty cells look different.
+    pub(crate) fn shows_progress(&self) -> bool {
+        self.fill != self.empty || self.head.is_some() || !self.partials.is_empty()
+    }
+
+    /// Columns used by the widest character of the style.
+    fn cell_width(&self) -> usize {
+        let mut chars = vec![self.fill, self.empty];
+        chars.extend(self.head);
+        chars.extend(&self.partials);
+        chars.into_iter().map(char_width).max().unwrap_or(1).max(1)
+    }
+
+    fn render_cells(&self, position: u64, total: u64, columns: usize) -> String {
+        let steps = self.partials.len() as u64 + 1;
+        let filled = (columns as u64 * steps * position.min(total))
+            .checked_div(total)
+            .unwrap_or(columns as u64 * steps);
+        let full = (filled / steps) as usize;
+        let mut bar: String = std::iter::repeat_n(self.fill, full).collect();
+        if full < columns {
+            let rest = (filled % steps) as usize;
+            let cell = match self.head {
+                _ if rest > 0 => self.partials[self.partials.len() - rest],
+                Some(head) if position > 0 => head,
+                _ => self.empty,
+            };
+            bar.push(cell);
+            bar.extend(std::iter::repeat_n(self.empty, columns - full - 1));
+        }
+        bar
+    }
+}
+
+/// Ready-made looks for a bar, see [`ProgressBar::with_preset`](crate::ProgressBar::with_preset).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Preset {
+    /// The default look: `[▓▓▓░░░]`.
+    Classic,
+    /// ASCII only, for terminals without unicode support: `[===>  ]`.
+    Ascii,
+    /// Block gradient with eighth-of-a-cell resolution: `│███▌  │`.
+    Blocks,
+    /// Braille dots without delimiters: `⣿⣿⣿⡆⠀⠀`.
+    Braille,
+    /// Percentage only, no bar: ` 42%`.
+    Minimal,
+}
+
+impl Preset {
+    pub(crate) fn style(&self) -> BarStyle {
+        match self {
+            Preset::Classic | Preset::Minimal => BarStyle::default(),
+            Preset::Ascii => BarStyle::new("= ").with_head('>'),
+            Preset::Blocks => BarStyle::new("█▉▊▋▌▍▎▏ "),
+            Preset::Braille => BarStyle::new("⣿⣷⣧⣇⡇⡆⡄⡀⠀"),
+        }
+    }
+
+    /// Delimiters of the preset, `None` for no delimiters.
+    pub(crate) fn delims(&self) -> Option<(char, char)> {
+        match self {
+            Preset::Classic | Preset::Ascii | Preset::Minimal => Some(('[', ']')),
+            Preset::Blocks => Some(('│', '│')),
+            Preset::Braille => None,
+        }
+    }
+
+    pub(crate) fn template(&self) -> Option<&'static str> {
+        match self {
+            Preset::Minimal => Some("{percent}%"),
+            _ => None,
+        }
+    }
+}
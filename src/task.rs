@@ -0,0 +1,131 @@
+//!
+//! Timed phases of the work of a progress bar.
+//!
+use crate::state::{lock, BarState, Timing};
+use crate::template::Template;
+use crate::tick::Ticker;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Template of the line of a running task.
+const TASK_TEMPLATE: &str = "  {spinner} {prefix} {pos} {elapsed} {msg}";
+
+/// Interval at which the line of a running task is redrawn.
+const TASK_TICK: Duration = Duration::from_millis(100);
+
+/// Time spent in the tasks of a name, see
+/// [`ProgressBar::phases`](crate::ProgressBar::phases).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Phase {
+    /// Name of the tasks.
+    pub name: String,
+    /// Number of tasks of this name that ended.
+    pub count: u64,
+    /// Time spent in them.
+    pub total: Duration,
+}
+
+impl Phase {
+    /// Counts a task of this phase lasting `elapsed`.
+    pub(crate) fn record(phases: &mut Vec<Phase>, name: String, elapsed: Duration) {
+        match phases.iter_mut().find(|phase| phase.name == name) {
+            Some(phase) => {
+                phase.count += 1;
+                phase.total += elapsed;
+            }
+            None => phases.push(Phase {
+                name,
+                count: 1,
+                total: elapsed,
+            }),
+        }
+    }
+}
+
+/// Guard timing a phase of the work of a bar, created by
+/// [`ProgressBar::task`](crate::ProgressBar::task). The phase ends when the
+/// guard is dropped and its duration is recorded by the bar, see
+/// [`ProgressBar::phases`](crate::ProgressBar::phases).
+///
+/// While it runs, a task of a bar added to a
+/// [`MultiProgress`](crate::MultiProgress) has its own line below the bars,
+/// with a spinner, the count of [`Task::inc`] and the elapsed time. The line
+/// is removed when the task ends. Tasks of standalone bars are only timed.
+pub struct Task {
+    name: String,
+    timing: Timing,
+    parent: Arc<Mutex<BarState>>,
+    state: Arc<Mutex<BarState>>,
+    ticker: Option<Ticker>,
+}
+
+impl Task {
+    pub(crate) fn start(parent: &Arc<Mutex<BarState>>, name: &str) -> Self {
+        let mut state = BarState::new();
+        let live = {
+            let parent = lock(parent);
+            state.durations = parent.durations;
+            state.hidden = parent.hidden || parent.target.multi.is_none();
+            state.target.multi = parent.target.multi.clone();
+            state.timing = Timing::new(parent.timing.clock());
+            !state.hidden
+        };
+        state.template = Some(Template::parse(TASK_TEMPLATE));
+        state.prefix = name.to_string();
+        state.tick(0);
+        let timing = state.timing.clone();
+        let state = state.shared();
+        Self {
+            name: name.to_string(),
+            timing,
+            parent: Arc::clone(parent),
+            ticker: match live {
+                true => Some(Ticker::spawn(&state, TASK_TICK)),
+                false => None,
+            },
+            state,
+        }
+    }
+
+    /// Name of the phase.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Time since the task started.
+    pub fn elapsed(&self) -> Duration {
+        self.timing.elapsed()
+    }
+
+    /// Counts `delta` more units of work done by the task.
+    pub fn inc(&self, delta: u64) {
+        lock(&self.state).tick(delta);
+    }
+
+    /// Replaces the message displayed on the line of the task.
+    pub fn set_message(&self, message: &str) {
+        let mut state = lock(&self.state);
+        state.message = message.to_string();
+        state.draw();
+    }
+}
+
+impl Drop for Task {
+    fn drop(&mut self) {
+        if let Some(mut ticker) = self.ticker.take() {
+            ticker.stop();
+        }
+        {
+            let mut state = lock(&self.state);
+            state.finished = true;
+            if let Some(multi) = &state.target.multi {
+                if !state.hidden {
+                    lock(multi).remove(state.id);
+                }
+            }
+        }
+        let elapsed = self.elapsed();
+        let name = std::mem::take(&mut self.name);
+        Phase::record(&mut lock(&self.parent).phases, name, elapsed);
+    }
+}
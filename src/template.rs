@@ -0,0 +1,428 @@
+//!
+//! Line templates. A template is a string with `{field}` placeholders that get
+//! replaced with the current progress values on every frame.
+//!
+use crate::format::{display_width, fit, DurationFormat};
+use std::time::Duration;
+
+/// Default template used by bounded progress bars.
+pub(crate) const BOUNDED_TEMPLATE: &str = "{percent}% {bar} {pos}/{len} {elapsed}";
+
+/// Default template used by unbounded progress bars.
+pub(crate) const UNBOUNDED_TEMPLATE: &str = "[{pos} in {elapsed}] ";
+
+/// Default template of unbounded bars counting items of a unit, see `with_unit`.
+pub(crate) const COUNTER_TEMPLATE: &str = "{pos} {unit}  {rate}";
+
+/// Template of heartbeat bars, which show no position.
+pub(crate) const HEARTBEAT_TEMPLATE: &str = "{spinner} {elapsed} {msg}";
+
+/// Frames of the `{spinner}` field, one per tick.
+const SPINNER: [char; 10] = ['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
+
+/// Spinner frame shown after `ticks` ticks.
+pub(crate) fn spinner(ticks: usize) -> char {
+    SPINNER[ticks % SPINNER.len()]
+}
+
+/// Fields that can be used inside a template.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Field {
+    Percent,
+    Bar,
+    Pos,
+    Len,
+    Elapsed,
+    Eta,
+    Rate,
+    Message,
+    Errors,
+    Queue,
+    Prefix,
+    Spinner,
+    Unit,
+}
+
+impl Field {
+    fn parse(name: &str) -> Option<Self> {
+        match name {
+            "percent" => Some(Field::Percent),
+            "bar" => Some(Field::Bar),
+            "pos" => Some(Field::Pos),
+            "len" => Some(Field::Len),
+            "elapsed" => Some(Field::Elapsed),
+            "eta" => Some(Field::Eta),
+            "rate" => Some(Field::Rate),
+            "msg" => Some(Field::Message),
+            "errors" => Some(Field::Errors),
+            "queue" => Some(Field::Queue),
+            "prefix" => Some(Field::Prefix),
+            "spinner" => Some(Field::Spinner),
+            "unit" => Some(Field::Unit),
+            _ => None,
+        }
+    }
+}
+
+impl Field {
+    /// Whether the field holds a number, right-aligned in its column.
+    fn is_numeric(&self) -> bool {
+        matches!(
+            self,
+            Field::Percent
+                | Field::Pos
+                | Field::Len
+                | Field::Elapsed
+                | Field::Eta
+                | Field::Rate
+                | Field::Errors
+                | Field::Queue
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Piece {
+    Text(String),
+    Field(Field),
+    /// Field rendered by a component, kept as its source text when there is none.
+    Custom { name: String, source: String },
+}
+
+/// Column given to a field by a `{field:spec}` placeholder.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Width {
+    /// Exactly this many columns, `None` aligning numbers right and text left.
+    /// Over-long text is truncated, numbers never are.
+    Fixed(usize, Option<Align>),
+    /// Whatever the line has left, truncated so the line fits its width.
+    Elastic,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Align {
+    Left,
+    Right,
+}
+
+impl Width {
+    /// Parses `12`, `<12`, `>12` or `*`.
+    fn parse(spec: &str) -> Option<Self> {
+        if spec == "*" {
+            return Some(Width::Elastic);
+        }
+        let (align, columns) = match spec.chars().next()? {
+            '<' => (Some(Align::Left), &spec[1..]),
+            '>' => (Some(Align::Right), &spec[1..]),
+            _ => (None, spec),
+        };
+        Some(Width::Fixed(columns.parse().ok()?, align))
+    }
+}
+
+/// Values available to a template when rendering a frame.
+pub(crate) struct Values {
+    pub(crate) percent: Option<usize>,
+    pub(crate) bar: String,
+    pub(crate) pos: String,
+    pub(crate) len: String,
+    pub(crate) elapsed: Duration,
+    pub(crate) eta: Option<Duration>,
+    pub(crate) rate: String,
+    pub(crate) message: String,
+    pub(crate) errors: u64,
+    /// Current frame of the spinner.
+    pub(crate) spinner: char,
+    /// Label of the bar, padded to the alignment width.
+    pub(crate) prefix: String,
+    /// Name of the items counted, see `with_unit`.
+    pub(crate) unit: String,
+    /// Items read ahead and waiting, see `with_prefetch`.
+    pub(crate) queue: usize,
+    /// Text of the custom fields, rendered by components.
+    pub(crate) custom: Vec<(String, String)>,
+    /// Columns available to the line, shared by the elastic fields.
+    pub(crate) line_width: usize,
+}
+
+/// A parsed template.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Template {
+    pieces: Vec<(Piece, Option<Width>)>,
+}
+
+impl Template {
+    /// Parses a template. Unknown fields are left to components and kept as
+    /// plain text when there is none, `{{`/`}}` can be used to output literal
+    /// braces. A field can be given a column with `{field:spec}`, see `Width`.
+    pub(crate) fn parse(source: &str) -> Self {
+        let mut pieces = Vec::new();
+        let mut text = String::new();
+        let mut chars = source.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    text.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    text.push('}');
+                }
+                '{' => {
+                    let mut name = String::new();
+                    let mut closed = false;
+                    for c in chars.by_ref() {
+                        if c == '}' {
+                            closed = true;
+                            break;
+                        }
+                        name.push(c);
+                    }
+                    if closed {
+                        if !text.is_empty() {
+                            pieces.push((Piece::Text(std::mem::take(&mut text)), None));
+                        }
+                        let source = format!("{{{}}}", name);
+                        let (name, width) = match name.split_once(':') {
+                            Some((field, spec)) => match Width::parse(spec) {
+                                Some(width) => (field.to_string(), Some(width)),
+                                None => (name, None),
+                            },
+                            None => (name, None),
+                        };
+                        let piece = match Field::parse(&name) {
+                            Some(field) => Piece::Field(field),
+                            None => Piece::Custom { name, source },
+                        };
+                        pieces.push((piece, width));
+                    } else {
+                        text.push('{');
+                        text.push_str(&name);
+                    }
+                }
+                c => text.push(c),
+            }
+        }
+        if !text.is_empty() {
+            pieces.push((Piece::Text(text), None));
+        }
+        Self { pieces }
+    }
+
+    /// Whether a field shares the columns left on the line, the only case the
+    /// width of the line is needed for.
+    pub(crate) fn is_elastic(&self) -> bool {
+        self.pieces
+            .iter()
+            .any(|(_, width)| *width == Some(Width::Elastic))
+    }
+
+    /// Renders a frame replacing each field with its value. Fields with a
+    /// fixed width are padded to it, then the elastic fields share the
+    /// columns left on the line and are truncated to their share.
+    pub(crate) fn render(&self, values: &Values, durations: DurationFormat) -> String {
+        let mut texts: Vec<String> = Vec::with_capacity(self.pieces.len());
+        for (piece, width) in &self.pieces {
+            let text = Self::render_piece(piece, values, durations);
+            texts.push(match (piece, width) {
+                // Left as they were written when no component renders them.
+                (Piece::Custom { source, .. }, _) if text == *source => text,
+                (Piece::Field(field), Some(Width::Fixed(columns, align))) => {
+                    let right = align.map_or(field.is_numeric(), |align| align == Align::Right);
+                    align_to(text, *columns, right, !field.is_numeric())
+                }
+                (_, Some(Width::Fixed(columns, align))) => {
+                    align_to(text, *columns, *align == Some(Align::Right), true)
+                }
+                _ => text,
+            });
+        }
+        let elastic = self
+            .pieces
+            .iter()
+            .filter(|(_, width)| *width == Some(Width::Elastic))
+            .count();
+        let used: usize = self
+            .pieces
+            .iter()
+            .zip(&texts)
+            .filter(|((_, width), _)| *width != Some(Width::Elastic))
+            .map(|(_, text)| display_width(text))
+            .sum();
+        if let Some(share) = values.line_width.saturating_sub(used).checked_div(elastic) {
+            for ((_, width), text) in self.pieces.iter().zip(texts.iter_mut()) {
+                if *width == Some(Width::Elastic) && display_width(text) > share {
+                    *text = fit(text, share);
+                }
+            }
+        }
+        texts.concat()
+    }
+
+    fn render_piece(piece: &Piece, values: &Values, durations: DurationFormat) -> String {
+        match piece {
+            Piece::Text(text) => text.clone(),
+            Piece::Field(Field::Percent) => match values.percent {
+                Some(percent) => format!("{:3}", percent),
+                None => "  ?".to_string(),
+            },
+            Piece::Field(Field::Bar) => values.bar.clone(),
+            Piece::Field(Field::Pos) => values.pos.clone(),
+            Piece::Field(Field::Len) => values.len.clone(),
+            Piece::Field(Field::Elapsed) => durations.format(values.elapsed),
+            Piece::Field(Field::Eta) => match values.eta {
+                Some(eta) => durations.format(eta),
+                None => "?".to_string(),
+            },
+            Piece::Field(Field::Rate) => values.rate.clone(),
+            Piece::Field(Field::Message) => values.message.clone(),
+            Piece::Field(Field::Errors) => values.errors.to_string(),
+            Piece::Field(Field::Queue) => values.queue.to_string(),
+            Piece::Field(Field::Prefix) => values.prefix.clone(),
+            Piece::Field(Field::Spinner) => values.spinner.to_string(),
+            Piece::Field(Field::Unit) => values.unit.clone(),
+            Piece::Custom { name, source } => {
+                match values.custom.iter().find(|(field, _)| field == name) {
+                    Some((_, text)) => text.clone(),
+                    None => source.clone(),
+                }
+            }
+        }
+    }
+}
+
+/// Pads `text` to `columns`, on the left when `right` aligned. Text longer
+/// than the column is truncated if `truncate`, kept whole otherwise.
+fn align_to(text: String, columns: usize, right: bool, truncate: bool) -> String {
+    let width = display_width(&text);
+    if width > columns {
+        return match truncate {
+            true => fit(&text, columns),
+            false => text,
+        };
+    }
+    let padding = " ".repeat(columns - width);
+    match right {
+        true => padding + &text,
+        false => text + &padding,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn values() -> Values {
+        Values {
+            percent: Some(42),
+            bar: "####----".to_string(),
+            pos: "42".to_string(),
+            len: "100".to_string(),
+            elapsed: Duration::from_secs(5),
+            eta: None,
+            rate: "8.40 it/s".to_string(),
+            message: "downloading".to_string(),
+            errors: 0,
+            spinner: spinner(0),
+            prefix: String::new(),
+            unit: "files".to_string(),
+            queue: 0,
+            custom: Vec::new(),
+            line_width: 80,
+        }
+    }
+
+    fn render(source: &str, values: &Values) -> String {
+        Template::parse(source).render(values, DurationFormat::Hms)
+    }
+
+    #[test]
+    fn fields_are_replaced() {
+        assert_eq!(
+            render("{percent}% {bar} {pos}/{len} {elapsed} eta {eta}", &values()),
+            " 42% ####---- 42/100 00:00:05 eta ?"
+        );
+        let starting = Values {
+            percent: None,
+            pos: "0".to_string(),
+            ..values()
+        };
+        assert_eq!(render("{percent}% {pos}/{len}", &starting), "  ?% 0/100");
+    }
+
+    #[test]
+    fn braces_are_escaped_or_kept() {
+        assert_eq!(render("{{pos}} {pos}", &values()), "{pos} 42");
+        assert_eq!(render("{pos} {unclosed", &values()), "42 {unclosed");
+    }
+
+    #[test]
+    fn widths_are_parsed() {
+        assert_eq!(Width::parse("6"), Some(Width::Fixed(6, None)));
+        assert_eq!(Width::parse("<4"), Some(Width::Fixed(4, Some(Align::Left))));
+        assert_eq!(Width::parse(">0"), Some(Width::Fixed(0, Some(Align::Right))));
+        assert_eq!(Width::parse("*"), Some(Width::Elastic));
+        assert_eq!(Width::parse(""), None);
+        assert_eq!(Width::parse("<"), None);
+        assert_eq!(Width::parse("-3"), None);
+    }
+
+    #[test]
+    fn fixed_widths_align_numbers_right_and_text_left() {
+        assert_eq!(render("[{pos:6}]", &values()), "[    42]");
+        assert_eq!(render("[{pos:<6}]", &values()), "[42    ]");
+        assert_eq!(render("[{msg:14}]", &values()), "[downloading   ]");
+        assert_eq!(render("[{msg:>14}]", &values()), "[   downloading]");
+    }
+
+    #[test]
+    fn fixed_widths_truncate_text_but_not_numbers() {
+        assert_eq!(render("[{msg:<4}]", &values()), "[dow…]");
+        assert_eq!(render("[{len:2}]", &values()), "[100]");
+    }
+
+    #[test]
+    fn elastic_fields_share_the_line() {
+        let narrow = Values {
+            line_width: 12,
+            ..values()
+        };
+        assert_eq!(render("{pos}/{len} {msg:*}", &narrow), "42/100 down…");
+        // Short enough values are not padded.
+        assert_eq!(render("{pos}/{len} {msg:*}", &values()), "42/100 downloading");
+        let full = Values {
+            line_width: 6,
+            ..values()
+        };
+        assert_eq!(render("{pos}/{len} {msg:*}", &full), "42/100 ");
+    }
+
+    #[test]
+    fn unknown_fields_are_left_literal() {
+        assert_eq!(render("{pos} {speed}", &values()), "42 {speed}");
+        assert_eq!(render("{pos} {speed:6}", &values()), "42 {speed:6}");
+        assert_eq!(render("{pos:wide}", &values()), "{pos:wide}");
+        let custom = Values {
+            custom: vec![("speed".to_string(), "fast".to_string())],
+            ..values()
+        };
+        assert_eq!(render("[{speed:6}]", &custom), "[fast  ]");
+    }
+
+    #[test]
+    fn align_to_pads_or_truncates() {
+        assert_eq!(align_to("ab".to_string(), 4, true, true), "  ab");
+        assert_eq!(align_to("ab".to_string(), 4, false, true), "ab  ");
+        assert_eq!(align_to("abcdef".to_string(), 4, false, true), "abc…");
+        assert_eq!(align_to("abcdef".to_string(), 4, true, false), "abcdef");
+        assert_eq!(align_to("日本".to_string(), 5, true, true), " 日本");
+        assert_eq!(align_to(String::new(), 0, false, true), "");
+    }
+
+    #[test]
+    fn spinner_cycles() {
+        assert_eq!(spinner(0), spinner(SPINNER.len()));
+        assert_ne!(spinner(0), spinner(1));
+    }
+}
@@ -0,0 +1,246 @@
+//!
+//! Terminal operations of the console renderer: erasing lines, moving the
+//! cursor, colors and the width of the terminal. They are written as ANSI
+//! escape sequences, which need no dependency and are understood by every
+//! terminal in use today, the Windows console included since Windows 10.
+//!
+//! The width is queried from the terminal itself with `ioctl(TIOCGWINSZ)`
+//! on Unix. Elsewhere, or when the output is not a terminal, it comes from
+//! the `COLUMNS` environment variable.
+//!
+//! With the `crossterm` feature the sequences are written by crossterm
+//! commands instead, the width is queried with `crossterm::terminal::size`
+//! and virtual terminal processing is enabled on Windows consoles that
+//! need it.
+//!
+//! Terminal libraries can take over the whole output instead, through
+//! [`ext::DrawTarget`](crate::ext::DrawTarget).
+//!
+use std::cell::Cell;
+use std::time::{Duration, Instant};
+
+#[cfg(not(feature = "crossterm"))]
+pub(crate) use ansi::*;
+#[cfg(feature = "crossterm")]
+pub(crate) use commands::*;
+
+/// Foreground color codes.
+pub(crate) const RED: u8 = 31;
+pub(crate) const GREEN: u8 = 32;
+
+#[cfg(not(feature = "crossterm"))]
+mod ansi {
+    /// Moves the cursor up to the bar line and erases it.
+    pub(crate) const ERASE_LINE: &str = "\x1b[1A\x1b[2K";
+
+    /// Moves the cursor back to the start of the current line and erases it.
+    pub(crate) const ERASE_CURRENT_LINE: &str = "\r\x1b[2K";
+
+    /// Shows the cursor again and resets the scroll region to the whole screen.
+    pub(crate) const RESTORE: &str = "\x1b[?25h\x1b[r";
+
+    /// Resets the colors.
+    const RESET: &str = "\x1b[0m";
+
+    /// Moves the cursor up `lines` lines and clears the screen from there.
+    pub(crate) fn erase_lines(lines: usize) -> String {
+        format!("\x1b[{}A\x1b[0J", lines)
+    }
+
+    /// Paints `text` with the foreground color `code`.
+    pub(crate) fn paint(text: &str, code: u8) -> String {
+        format!("\x1b[{}m{}{}", code, text, RESET)
+    }
+
+    /// Prepares the console for the sequences, nothing to do for plain ANSI.
+    pub(crate) fn enable() {}
+}
+
+#[cfg(feature = "crossterm")]
+mod commands {
+    use crossterm::cursor::{MoveUp, Show};
+    use crossterm::style::{Color, ResetColor, SetForegroundColor};
+    use crossterm::terminal::{Clear, ClearType};
+    use crossterm::Command;
+    use std::fmt;
+
+    /// Terminal operation written as the sequences of crossterm commands.
+    #[derive(Debug, Clone, Copy)]
+    pub(crate) enum Sequence {
+        EraseLine,
+        EraseCurrentLine,
+        Restore,
+    }
+
+    /// Moves the cursor up to the bar line and erases it.
+    pub(crate) const ERASE_LINE: Sequence = Sequence::EraseLine;
+
+    /// Moves the cursor back to the start of the current line and erases it.
+    pub(crate) const ERASE_CURRENT_LINE: Sequence = Sequence::EraseCurrentLine;
+
+    /// Shows the cursor again and resets the scroll region to the whole screen.
+    pub(crate) const RESTORE: Sequence = Sequence::Restore;
+
+    impl fmt::Display for Sequence {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self {
+                Sequence::EraseLine => {
+                    MoveUp(1).write_ansi(f)?;
+                    Clear(ClearType::CurrentLine).write_ansi(f)
+                }
+                Sequence::EraseCurrentLine => {
+                    f.write_str("\r")?;
+                    Clear(ClearType::CurrentLine).write_ansi(f)
+                }
+                Sequence::Restore => {
+                    Show.write_ansi(f)?;
+                    // crossterm has no command resetting the scroll region.
+                    f.write_str("\x1b[r")
+                }
+            }
+        }
+    }
+
+    /// Moves the cursor up `lines` lines and clears the screen from there.
+    pub(crate) fn erase_lines(lines: usize) -> String {
+        let mut sequence = String::new();
+        let _ = MoveUp(lines as u16).write_ansi(&mut sequence);
+        let _ = Clear(ClearType::FromCursorDown).write_ansi(&mut sequence);
+        sequence
+    }
+
+    /// Paints `text` with the foreground color `code`, an ANSI color code
+    /// from `30` to `37` or `90` to `97`.
+    pub(crate) fn paint(text: &str, code: u8) -> String {
+        let color = match code {
+            30 => Color::Black,
+            31 => Color::DarkRed,
+            32 => Color::DarkGreen,
+            33 => Color::DarkYellow,
+            34 => Color::DarkBlue,
+            35 => Color::DarkMagenta,
+            36 => Color::DarkCyan,
+            37 => Color::Grey,
+            90 => Color::DarkGrey,
+            91 => Color::Red,
+            92 => Color::Green,
+            93 => Color::Yellow,
+            94 => Color::Blue,
+            95 => Color::Magenta,
+            96 => Color::Cyan,
+            97 => Color::White,
+            // Not a color crossterm knows about, written as it is.
+            _ => return format!("\x1b[{}m{}\x1b[0m", code, text),
+        };
+        let mut painted = String::new();
+        let _ = SetForegroundColor(color).write_ansi(&mut painted);
+        painted.push_str(text);
+        let _ = ResetColor.write_ansi(&mut painted);
+        painted
+    }
+
+    /// Enables the processing of the sequences on Windows consoles that do
+    /// not do it by default.
+    pub(crate) fn enable() {
+        #[cfg(windows)]
+        crossterm::ansi_support::supports_ansi();
+    }
+
+    pub(super) fn query_width() -> Option<usize> {
+        crossterm::terminal::size().ok().map(|(columns, _)| columns as usize)
+    }
+}
+
+/// Interval after which a cached width is queried again, following resizes.
+const WIDTH_REFRESH: Duration = Duration::from_secs(1);
+
+/// Width of the terminal, queried at most once per `WIDTH_REFRESH`.
+#[derive(Debug, Default)]
+pub(crate) struct CachedWidth(Cell<Option<(Instant, usize)>>);
+
+impl CachedWidth {
+    /// Width of the terminal at `now`, queried again if the cached one is old.
+    pub(crate) fn get(&self, now: Instant) -> usize {
+        match self.0.get() {
+            Some((queried, width)) if now.saturating_duration_since(queried) < WIDTH_REFRESH => {
+                width
+            }
+            _ => {
+                let width = width();
+                self.0.set(Some((now, width)));
+                width
+            }
+        }
+    }
+}
+
+/// Width of the terminal lines, as reported by the terminal the output or
+/// the errors are attached to. Falls back to the `COLUMNS` environment
+/// variable when none is a terminal, then to 80 columns.
+pub(crate) fn width() -> usize {
+    query_width()
+        .or_else(|| {
+            std::env::var("COLUMNS")
+                .ok()
+                .and_then(|columns| columns.trim().parse().ok())
+        })
+        .filter(|columns| *columns > 0)
+        .unwrap_or(80)
+}
+
+#[cfg(all(unix, not(feature = "crossterm")))]
+fn query_width() -> Option<usize> {
+    use std::os::raw::{c_int, c_ulong, c_ushort};
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    const TIOCGWINSZ: c_ulong = 0x5413;
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    const TIOCGWINSZ: c_ulong = 0x4008_7468;
+
+    #[repr(C)]
+    #[derive(Default)]
+    struct WinSize {
+        rows: c_ushort,
+        columns: c_ushort,
+        x_pixels: c_ushort,
+        y_pixels: c_ushort,
+    }
+
+    extern "C" {
+        fn ioctl(fd: c_int, request: c_ulong, ...) -> c_int;
+    }
+
+    // Standard output first, it is where the bar is drawn.
+    [1, 2].iter().find_map(|&fd| {
+        let mut size = WinSize::default();
+        match unsafe { ioctl(fd, TIOCGWINSZ, &mut size as *mut WinSize) } {
+            0 if size.columns > 0 => Some(size.columns as usize),
+            _ => None,
+        }
+    })
+}
+
+#[cfg(not(any(unix, feature = "crossterm")))]
+fn query_width() -> Option<usize> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::strip_ansi;
+
+    #[test]
+    fn backends_write_the_same_cursor_moves() {
+        assert_eq!(ERASE_LINE.to_string(), "\x1b[1A\x1b[2K");
+        assert_eq!(ERASE_CURRENT_LINE.to_string(), "\r\x1b[2K");
+        assert!(erase_lines(3).starts_with("\x1b[3A"));
+        assert!(RESTORE.to_string().starts_with("\x1b[?25h"));
+    }
+
+    #[test]
+    fn painted_text_keeps_its_width() {
+        assert_eq!(strip_ansi(&paint("ok", GREEN)), "ok");
+        assert_eq!(strip_ansi(&paint("failed", 99)), "failed");
+    }
+}
@@ -0,0 +1,181 @@
+//!
+//! Deterministic rendering for snapshot tests of progress bars.
+//!
+//! A [`MockClock`] only moves when told so and a [`Capture`] records the frames
+//! in memory instead of drawing them, so the output of a bar is the same on
+//! every run, whatever the speed of the machine running the test:
+//!
+//! ```
+//! use cpbar::testing::{Capture, MockClock};
+//! use cpbar::*;
+//! use std::time::Duration;
+//!
+//! let clock = MockClock::new();
+//! let capture = Capture::new();
+//! let progress_bar = ProgressBar::new(0..4)
+//!     .with_bounds()
+//!     .with_template("{pos}/{len} {elapsed} eta {eta}")
+//!     .with_duration_format(DurationFormat::Hms)
+//!     .with_clock(clock.clone())
+//!     .with_draw_target(capture.clone());
+//! for _ in progress_bar {
+//!     clock.advance(Duration::from_secs(30));
+//! }
+//! capture.assert_frames(&[
+//!     "0/4 00:00:00 eta ?",
+//!     "1/4 00:00:30 eta 00:01:30",
+//!     "2/4 00:01:00 eta 00:01:00",
+//!     "3/4 00:01:30 eta 00:00:30",
+//!     "4/4 00:02:00 eta 00:00:00",
+//! ]);
+//! assert!(capture.is_finished());
+//! ```
+//!
+use crate::ext::{Clock, DrawTarget};
+use crate::format::strip_ansi;
+use crate::state::lock;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Clock standing still until [`MockClock::advance`] is called, see
+/// [`ProgressBar::with_clock`](crate::ProgressBar::with_clock). Clones share
+/// the same time, so a test keeps one to drive the clock of the bar.
+#[derive(Debug, Clone)]
+pub struct MockClock {
+    origin: Instant,
+    offset: Arc<Mutex<Duration>>,
+}
+
+impl MockClock {
+    /// Creates a clock stopped at the current time.
+    pub fn new() -> Self {
+        Self {
+            origin: Instant::now(),
+            offset: Arc::new(Mutex::new(Duration::ZERO)),
+        }
+    }
+
+    /// Moves the clock `duration` forward.
+    pub fn advance(&self, duration: Duration) {
+        *lock(&self.offset) += duration;
+    }
+
+    /// Time the clock was moved forward by since it was created.
+    pub fn elapsed(&self) -> Duration {
+        *lock(&self.offset)
+    }
+}
+
+impl Default for MockClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> Instant {
+        self.origin + *lock(&self.offset)
+    }
+}
+
+/// What a bar sent to a [`Capture`].
+#[derive(Debug, Default)]
+struct Output {
+    frames: Vec<String>,
+    printed: Vec<String>,
+    finished: bool,
+}
+
+/// Draw target recording the output of a bar in memory, see
+/// [`ProgressBar::with_draw_target`](crate::ProgressBar::with_draw_target).
+/// Clones share the same recording. Frames are recorded without their
+/// colors, as they would be read on the screen.
+#[derive(Debug, Clone, Default)]
+pub struct Capture {
+    output: Arc<Mutex<Output>>,
+}
+
+impl Capture {
+    /// Creates an empty recording.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Every frame drawn so far, oldest first.
+    pub fn frames(&self) -> Vec<String> {
+        lock(&self.output).frames.clone()
+    }
+
+    /// Frame displayed last, `None` when nothing was drawn.
+    pub fn last_frame(&self) -> Option<String> {
+        lock(&self.output).frames.last().cloned()
+    }
+
+    /// Persistent lines printed above the bar, such as the summary.
+    pub fn printed(&self) -> Vec<String> {
+        lock(&self.output).printed.clone()
+    }
+
+    /// Whether the bar finished.
+    pub fn is_finished(&self) -> bool {
+        lock(&self.output).finished
+    }
+
+    /// Forgets everything recorded so far.
+    pub fn clear(&self) {
+        *lock(&self.output) = Output::default();
+    }
+
+    /// Panics unless the last frame is `expected`.
+    #[track_caller]
+    pub fn assert_last_frame(&self, expected: &str) {
+        let output = lock(&self.output);
+        match output.frames.last() {
+            Some(frame) if frame == expected => {}
+            Some(frame) => panic!(
+                "last frame differs\n  expected: {:?}\n    actual: {:?}",
+                expected, frame
+            ),
+            None => panic!("no frame drawn, expected: {:?}", expected),
+        }
+    }
+
+    /// Panics unless the frames drawn so far are `expected`, in this order.
+    #[track_caller]
+    pub fn assert_frames(&self, expected: &[&str]) {
+        let output = lock(&self.output);
+        if output
+            .frames
+            .iter()
+            .map(String::as_str)
+            .eq(expected.iter().copied())
+        {
+            return;
+        }
+        let mut report = String::from("frames differ");
+        for index in 0..output.frames.len().max(expected.len()) {
+            let actual = output.frames.get(index).map(String::as_str);
+            let wanted = expected.get(index).copied();
+            let marker = if actual == wanted { ' ' } else { '!' };
+            report.push_str(&format!(
+                "\n{} #{}\n  expected: {:?}\n    actual: {:?}",
+                marker, index, wanted, actual
+            ));
+        }
+        panic!("{}", report);
+    }
+}
+
+impl DrawTarget for Capture {
+    fn draw(&mut self, frame: &str) {
+        lock(&self.output).frames.push(strip_ansi(frame));
+    }
+
+    fn println(&mut self, line: &str) {
+        lock(&self.output).printed.push(strip_ansi(line));
+    }
+
+    fn finish(&mut self) {
+        lock(&self.output).finished = true;
+    }
+}
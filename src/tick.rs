@@ -0,0 +1,100 @@
+//!
+//! Background thread redrawing a bar at a fixed interval.
+//!
+use crate::state::{lock, BarState};
+use std::sync::{Arc, Condvar, Mutex, Weak};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+struct Control {
+    interval: Duration,
+    stopped: bool,
+}
+
+/// Redraws a bar periodically so the elapsed time keeps moving while a slow
+/// item is being processed. The thread stops when the bar finishes, when the
+/// ticker is dropped or when the bar state is gone.
+pub(crate) struct Ticker {
+    control: Arc<(Mutex<Control>, Condvar)>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Ticker {
+    pub(crate) fn spawn(state: &Arc<Mutex<BarState>>, interval: Duration) -> Self {
+        let control = Arc::new((
+            Mutex::new(Control {
+                interval,
+                stopped: false,
+            }),
+            Condvar::new(),
+        ));
+        let thread = {
+            let control = Arc::clone(&control);
+            let state = Arc::downgrade(state);
+            std::thread::spawn(move || run(&control, &state))
+        };
+        Self {
+            control,
+            thread: Some(thread),
+        }
+    }
+
+    /// Interval between two redraws.
+    pub(crate) fn interval(&self) -> Duration {
+        lock(&self.control.0).interval
+    }
+
+    /// Changes the interval of the running thread.
+    pub(crate) fn set_interval(&self, interval: Duration) {
+        let (control, wakeup) = &*self.control;
+        lock(control).interval = interval;
+        wakeup.notify_one();
+    }
+
+    /// Stops the thread and waits for it to exit.
+    pub(crate) fn stop(&mut self) {
+        let (control, wakeup) = &*self.control;
+        lock(control).stopped = true;
+        wakeup.notify_one();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for Ticker {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+fn run(control: &(Mutex<Control>, Condvar), state: &Weak<Mutex<BarState>>) {
+    let (control, wakeup) = control;
+    let mut guard = lock(control);
+    loop {
+        // Stopped before the thread got to wait, the wakeup is already gone.
+        if guard.stopped {
+            return;
+        }
+        let interval = guard.interval;
+        guard = wakeup
+            .wait_timeout(guard, interval)
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .0;
+        if guard.stopped {
+            return;
+        }
+        let state = match state.upgrade() {
+            Some(state) => state,
+            None => return,
+        };
+        let mut state = lock(&state);
+        if state.finished {
+            return;
+        }
+        // Nothing to refresh until the first item is requested.
+        if state.index > 0 {
+            state.refresh();
+        }
+    }
+}
@@ -0,0 +1,66 @@
+//!
+//! Bars filling over a fixed duration instead of a number of items.
+//!
+use crate::ext::SystemClock;
+use crate::state::Timing;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Iterator yielding the time elapsed since it was created until `duration`
+/// has passed or it is cancelled. Built by
+/// [`ProgressBar::for_duration`](crate::ProgressBar::for_duration), in which
+/// case it follows the clock of the bar.
+///
+/// # Example
+/// ```
+/// use cpbar::*;
+/// use std::time::Duration;
+///
+/// let mut deadline = Deadline::new(Duration::from_millis(20));
+/// assert!(deadline.next().unwrap() < Duration::from_millis(20));
+/// std::thread::sleep(Duration::from_millis(20));
+/// assert_eq!(deadline.next(), None);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Deadline {
+    timing: Timing,
+    duration: Duration,
+    cancelled: bool,
+}
+
+impl Deadline {
+    /// Starts a deadline `duration` from now.
+    pub fn new(duration: Duration) -> Self {
+        Self {
+            timing: Timing::new(Arc::new(SystemClock)),
+            duration,
+            cancelled: false,
+        }
+    }
+
+    /// Whether the duration has passed or the deadline was cancelled.
+    pub fn is_done(&self) -> bool {
+        self.cancelled || self.timing.elapsed() >= self.duration
+    }
+
+    /// Clock and start of the deadline, to share them with a bar.
+    pub(crate) fn timing(&self) -> Timing {
+        self.timing.clone()
+    }
+
+    /// Ends the deadline early.
+    pub fn cancel(&mut self) {
+        self.cancelled = true;
+    }
+}
+
+impl Iterator for Deadline {
+    type Item = Duration;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.is_done() {
+            true => None,
+            false => Some(self.timing.elapsed()),
+        }
+    }
+}
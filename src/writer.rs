@@ -0,0 +1,98 @@
+//!
+//! Writer adapter letting loggers print while bars are being drawn.
+//!
+use crate::handle::ProgressHandle;
+use crate::multi::MultiProgress;
+use std::io::{self, Write};
+
+/// Display erased while output is written: a single bar or a multi-bar display.
+pub(crate) enum Target {
+    Bar(ProgressHandle),
+    Multi(MultiProgress),
+}
+
+impl Target {
+    pub(crate) fn suspend<R>(&self, f: impl FnOnce() -> R) -> R {
+        match self {
+            Target::Bar(handle) => handle.suspend(f),
+            Target::Multi(multi) => multi.suspend(f),
+        }
+    }
+}
+
+/// Wraps the writer of a logger so that each complete line is written while
+/// the bars are erased, then the bars are redrawn below it. Plug it wherever
+/// a logger accepts a custom `Write` target to keep log records from
+/// corrupting the progress display.
+///
+/// Output is buffered until a newline, and whatever remains is written when
+/// the writer is flushed or dropped.
+///
+/// # Example
+/// ```
+/// use cpbar::*;
+/// use std::io::Write;
+///
+/// let progress_bar = ProgressBar::new((0..3)).with_bounds();
+/// let mut log = progress_bar.handle().writer(std::io::stderr());
+/// for i in progress_bar {
+///     writeln!(log, "[INFO] processing item {}", i).unwrap();
+/// }
+/// ```
+pub struct ProgressWriter<W: Write> {
+    target: Target,
+    inner: W,
+    buffer: Vec<u8>,
+}
+
+impl<W: Write> ProgressWriter<W> {
+    pub(crate) fn for_bar(handle: ProgressHandle, inner: W) -> Self {
+        Self::new(Target::Bar(handle), inner)
+    }
+
+    pub(crate) fn for_multi(multi: MultiProgress, inner: W) -> Self {
+        Self::new(Target::Multi(multi), inner)
+    }
+
+    fn new(target: Target, inner: W) -> Self {
+        Self {
+            target,
+            inner,
+            buffer: Vec::new(),
+        }
+    }
+
+    /// Writes `bytes` to the inner writer with the bars erased.
+    fn write_suspended(&mut self, bytes: &[u8]) -> io::Result<()> {
+        let inner = &mut self.inner;
+        self.target.suspend(|| {
+            inner.write_all(bytes)?;
+            inner.flush()
+        })
+    }
+}
+
+impl<W: Write> Write for ProgressWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buffer.extend_from_slice(buf);
+        if let Some(end) = self.buffer.iter().rposition(|byte| *byte == b'\n') {
+            let lines: Vec<u8> = self.buffer.drain(..=end).collect();
+            self.write_suspended(&lines)?;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if !self.buffer.is_empty() {
+            let rest = std::mem::take(&mut self.buffer);
+            self.write_suspended(&rest)?;
+        }
+        Ok(())
+    }
+}
+
+impl<W: Write> Drop for ProgressWriter<W> {
+    fn drop(&mut self) {
+        let _ = self.flush();
+    }
+}